@@ -0,0 +1,107 @@
+use anyhow::Context as _;
+use cargo_about::licenses;
+
+#[derive(clap::Subcommand, Debug)]
+pub enum Subcommand {
+    /// Lists every entry in the cache
+    List,
+    /// Removes entries from the cache
+    Prune {
+        /// Only removes entries that were written more than this many seconds
+        /// ago. Removes all entries if not specified.
+        #[clap(long)]
+        older_than: Option<u64>,
+    },
+    /// Retrieves and caches all of the remote data needed to generate output
+    /// for the crate or workspace, so that later runs can be done offline
+    Warm {
+        /// The confidence threshold required for license files
+        /// to be positively identified: 0.0 - 1.0
+        #[clap(long, default_value = "0.8")]
+        threshold: f32,
+    },
+}
+
+#[derive(clap::Parser, Debug)]
+pub struct Args {
+    #[clap(flatten)]
+    krates: crate::common::KrateArgs,
+    #[clap(subcommand)]
+    cmd: Subcommand,
+}
+
+/// Formats a duration in seconds in the largest whole unit
+fn format_age(secs: u64) -> String {
+    match secs {
+        s if s >= 24 * 60 * 60 => format!("{}d", s / (24 * 60 * 60)),
+        s if s >= 60 * 60 => format!("{}h", s / (60 * 60)),
+        s if s >= 60 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+pub fn cmd(args: Args) -> anyhow::Result<()> {
+    let manifest_path = args.krates.manifest_path()?;
+    let cfg = args.krates.config(&manifest_path)?;
+
+    let cache = licenses::cache::Cache::from_config(&cfg.cache)?
+        .context("the cache has been disabled in the config")?;
+
+    match args.cmd {
+        Subcommand::List => {
+            let now = licenses::cache::now();
+
+            for entry in cache.entries()? {
+                println!(
+                    "{:<16} {:<40} {:>10} {:>5}{}",
+                    entry.kind,
+                    entry.key,
+                    entry.size,
+                    format_age(now.saturating_sub(entry.modified)),
+                    entry
+                        .description
+                        .map(|desc| format!(" {}", desc))
+                        .unwrap_or_default(),
+                );
+            }
+        }
+        Subcommand::Prune { older_than } => {
+            let pruned = cache.prune(older_than)?;
+
+            for entry in &pruned {
+                log::debug!("removed {}", entry.path);
+            }
+
+            log::info!(
+                "removed {} entries ({} bytes) from {}",
+                pruned.len(),
+                pruned.iter().map(|e| e.size).sum::<u64>(),
+                cache.root()
+            );
+        }
+        Subcommand::Warm { threshold } => {
+            let (krates, store) = rayon::join(
+                || args.krates.krates(&manifest_path, &cfg),
+                || {
+                    log::info!("loading license store");
+                    licenses::store_from_cache()
+                },
+            );
+
+            let krates = krates?;
+            let store = store?;
+
+            log::info!("gathered {} crates", krates.len());
+
+            let client = cd::client::Client::new();
+            let summary = licenses::Gatherer::with_store(std::sync::Arc::new(store), client)
+                .with_confidence_threshold(threshold)
+                .with_cache(Some(cache))
+                .gather(&krates, &cfg);
+
+            log::info!("warmed the cache for {} crates", summary.len());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use anyhow::Context as _;
+use cargo_about::licenses::{
+    self,
+    resolution::{Diagnostic, Severity},
+};
+use codespan_reporting::term;
+use krates::Utf8PathBuf as PathBuf;
+use std::collections::HashMap;
+
+mod summary;
+
+/// The exit code when one or more errors were encountered
+pub const EXIT_ERRORS: i32 = 2;
+/// The exit code when one or more warnings, but no errors, were encountered
+pub const EXIT_WARNINGS: i32 = 3;
+
+/// The format of the machine readable summary
+#[derive(clap::ArgEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SummaryFormat {
+    /// A JSON document with the findings for each crate
+    Json,
+    /// A `JUnit` XML report, with a test case for each crate
+    Junit,
+    /// A SARIF 2.1.0 log
+    Sarif,
+}
+
+#[derive(clap::Parser, Debug)]
+pub struct Args {
+    #[clap(flatten)]
+    krates: crate::common::KrateArgs,
+    /// The confidence threshold required for license files
+    /// to be positively identified: 0.0 - 1.0
+    #[clap(long, default_value = "0.8")]
+    threshold: f32,
+    /// Emits a machine readable summary of the check in addition to the
+    /// diagnostics
+    #[clap(arg_enum, long, ignore_case = true)]
+    summary_format: Option<SummaryFormat>,
+    /// A file to write the summary to, defaults to stdout
+    #[clap(long, requires = "summary-format")]
+    summary_output: Option<PathBuf>,
+}
+
+/// The findings for a single crate
+struct Checked<'k> {
+    krate: &'k cargo_about::Krate,
+    /// The licenses that were chosen to satisfy the crate's license expression
+    licenses: Vec<spdx::LicenseReq>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+pub fn cmd(args: Args, color: crate::Color) -> anyhow::Result<i32> {
+    let manifest_path = args.krates.manifest_path()?;
+    let cfg = args.krates.config(&manifest_path)?;
+
+    let (krates, store) = rayon::join(
+        || args.krates.krates(&manifest_path, &cfg),
+        || {
+            log::info!("loading license store");
+            licenses::store_from_cache()
+        },
+    );
+
+    let krates = krates?;
+    let store = store?;
+
+    log::info!("gathered {} crates", krates.len());
+
+    let cache = licenses::cache::Cache::from_config(&cfg.cache)?;
+    let network = licenses::fetch::Network::new(args.krates.network_mode());
+
+    let client = cd::client::Client::new();
+    let gathered = licenses::Gatherer::with_store(std::sync::Arc::new(store), client)
+        .with_confidence_threshold(args.threshold)
+        .with_cache(cache)
+        .with_network(network.clone())
+        .gather(&krates, &cfg);
+
+    let (files, resolved) = licenses::resolution::resolve(&gathered, &cfg);
+
+    // Attribute any retrievals that were denied to the crate they were for
+    let mut denied: HashMap<_, Vec<_>> = HashMap::new();
+    for df in network.take_denied() {
+        let diag = crate::common::denied_diagnostic(&df, network.mode());
+        denied.entry(df.krate).or_default().push(diag);
+    }
+
+    let checked: Vec<_> = gathered
+        .iter()
+        .zip(resolved)
+        .filter_map(|(kl, resolved)| {
+            let resolved = resolved?;
+
+            let mut diagnostics = denied.remove(&kl.krate.to_string()).unwrap_or_default();
+            diagnostics.extend(resolved.diagnostics);
+
+            Some(Checked {
+                krate: kl.krate,
+                licenses: resolved.licenses,
+                diagnostics,
+            })
+        })
+        .collect();
+
+    // Denied retrievals for crates that were not gathered, which shouldn't
+    // happen, but still affect the outcome
+    let unattributed: Vec<_> = denied.into_values().flatten().collect();
+
+    let mut errors = 0;
+    let mut warnings = 0;
+
+    {
+        let stream = crate::common::diagnostic_stream(color);
+        let diag_cfg = term::Config::default();
+        let mut streaml = stream.lock();
+
+        for diag in checked
+            .iter()
+            .flat_map(|c| c.diagnostics.iter())
+            .chain(&unattributed)
+        {
+            match diag.severity {
+                Severity::Bug | Severity::Error => errors += 1,
+                Severity::Warning => warnings += 1,
+                Severity::Note | Severity::Help => {}
+            }
+
+            term::emit(&mut streaml, &diag_cfg, &files, diag)?;
+        }
+    }
+
+    if let Some(format) = args.summary_format {
+        let summary = summary::render(format, &checked, &unattributed, &files)?;
+
+        match &args.summary_output {
+            Some(path) if path != "-" => {
+                std::fs::write(path, summary)
+                    .with_context(|| format!("summary file {} could not be written", path))?;
+            }
+            _ => println!("{}", summary),
+        }
+    }
+
+    log::info!(
+        "checked {} crates: {} errors, {} warnings",
+        checked.len(),
+        errors,
+        warnings
+    );
+
+    Ok(if errors > 0 {
+        EXIT_ERRORS
+    } else if warnings > 0 {
+        EXIT_WARNINGS
+    } else {
+        0
+    })
+}
@@ -0,0 +1,474 @@
+//! Machine readable summaries of a check, for consumption by CI systems
+
+use super::{Checked, SummaryFormat};
+use crate::common::xml_escape as escape;
+use cargo_about::licenses::resolution::{Diagnostic, Files, Severity};
+use codespan_reporting::diagnostic::LabelStyle;
+use serde::Serialize;
+use std::fmt::Write as _;
+
+/// The version of the JSON summary schema, this is bumped any time a breaking
+/// change is made to it
+const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Location {
+    path: String,
+    /// The 1-based line
+    line: usize,
+    /// The 1-based column
+    column: usize,
+}
+
+#[derive(Serialize)]
+struct Finding<'a> {
+    severity: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'a str>,
+    message: &'a str,
+    notes: &'a [String],
+    location: Option<Location>,
+}
+
+#[derive(Serialize)]
+struct CrateSummary<'a> {
+    name: &'a str,
+    version: String,
+    id: &'a str,
+    licenses: Vec<String>,
+    findings: Vec<Finding<'a>>,
+}
+
+#[derive(Serialize)]
+struct Summary<'a> {
+    version: u32,
+    errors: usize,
+    warnings: usize,
+    crates: Vec<CrateSummary<'a>>,
+    /// Findings that couldn't be attributed to any of the crates
+    findings: Vec<Finding<'a>>,
+}
+
+#[inline]
+fn severity(sev: Severity) -> &'static str {
+    match sev {
+        Severity::Bug => "bug",
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+        Severity::Help => "help",
+    }
+}
+
+/// Gets the location of the primary label, or the first label if there is
+/// no primary label
+fn location(diag: &Diagnostic, files: &Files) -> Option<Location> {
+    let label = diag
+        .labels
+        .iter()
+        .find(|l| l.style == LabelStyle::Primary)
+        .or_else(|| diag.labels.first())?;
+
+    let loc = files
+        .location(label.file_id, label.range.start as u32)
+        .ok()?;
+
+    Some(Location {
+        path: files.name(label.file_id).to_string_lossy().into_owned(),
+        line: loc.line.to_usize() + 1,
+        column: loc.column.to_usize() + 1,
+    })
+}
+
+fn summarize<'a>(
+    checked: &'a [Checked<'_>],
+    unattributed: &'a [Diagnostic],
+    files: &Files,
+) -> Summary<'a> {
+    let mut errors = 0;
+    let mut warnings = 0;
+
+    let mut finding = |diag: &'a Diagnostic| {
+        match diag.severity {
+            Severity::Bug | Severity::Error => errors += 1,
+            Severity::Warning => warnings += 1,
+            Severity::Note | Severity::Help => {}
+        }
+
+        Finding {
+            severity: severity(diag.severity),
+            code: diag.code.as_deref(),
+            message: &diag.message,
+            notes: &diag.notes,
+            location: location(diag, files),
+        }
+    };
+
+    let crates = checked
+        .iter()
+        .map(|checked| {
+            let findings = checked.diagnostics.iter().map(&mut finding).collect();
+
+            CrateSummary {
+                name: &checked.krate.name,
+                version: checked.krate.version.to_string(),
+                id: &checked.krate.id.repr,
+                licenses: checked.licenses.iter().map(|l| l.to_string()).collect(),
+                findings,
+            }
+        })
+        .collect();
+
+    let findings = unattributed.iter().map(&mut finding).collect();
+
+    Summary {
+        version: SCHEMA_VERSION,
+        errors,
+        warnings,
+        crates,
+        findings,
+    }
+}
+
+/// Formats a finding as a single block of text
+fn describe(finding: &Finding<'_>) -> String {
+    let mut desc = format!("{}: {}", finding.severity, finding.message);
+    if let Some(loc) = &finding.location {
+        let _ = write!(desc, "\n  --> {}:{}:{}", loc.path, loc.line, loc.column);
+    }
+    for note in finding.notes {
+        let _ = write!(desc, "\n  = {}", note);
+    }
+    desc
+}
+
+/// Each crate is a test case, which fails if it has any errors. Warnings are
+/// included in the output, but don't fail the test case. Findings that aren't
+/// attributed to a crate are a test case of their own.
+fn junit(summary: &Summary<'_>) -> Result<String, std::fmt::Error> {
+    let mut out = String::new();
+
+    let cases: Vec<_> = summary
+        .crates
+        .iter()
+        .map(|krate| {
+            (
+                format!("{} {}", krate.name, krate.version),
+                krate.findings.as_slice(),
+            )
+        })
+        .chain(
+            (!summary.findings.is_empty())
+                .then(|| ("cargo-about".to_owned(), summary.findings.as_slice())),
+        )
+        .collect();
+
+    let failures = cases
+        .iter()
+        .filter(|(_, findings)| {
+            findings
+                .iter()
+                .any(|f| f.severity == "error" || f.severity == "bug")
+        })
+        .count();
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<testsuites name="cargo-about" tests="{}" failures="{}">"#,
+        cases.len(),
+        failures
+    )?;
+    writeln!(
+        out,
+        r#"  <testsuite name="licenses" tests="{}" failures="{}" errors="0" skipped="0">"#,
+        cases.len(),
+        failures
+    )?;
+
+    for (name, findings) in &cases {
+        let name = escape(name);
+
+        if findings.is_empty() {
+            writeln!(
+                out,
+                r#"    <testcase classname="licenses" name="{}"/>"#,
+                name
+            )?;
+            continue;
+        }
+
+        writeln!(
+            out,
+            r#"    <testcase classname="licenses" name="{}">"#,
+            name
+        )?;
+
+        let (failed, other): (Vec<_>, Vec<_>) = findings
+            .iter()
+            .partition(|f| f.severity == "error" || f.severity == "bug");
+
+        for finding in failed {
+            writeln!(
+                out,
+                r#"      <failure type="{}" message="{}">{}</failure>"#,
+                finding.severity,
+                escape(finding.message),
+                escape(&describe(finding))
+            )?;
+        }
+
+        if !other.is_empty() {
+            let text: Vec<_> = other.into_iter().map(describe).collect();
+            writeln!(
+                out,
+                "      <system-out>{}</system-out>",
+                escape(&text.join("\n"))
+            )?;
+        }
+
+        writeln!(out, "    </testcase>")?;
+    }
+
+    writeln!(out, "  </testsuite>")?;
+    writeln!(out, "</testsuites>")?;
+
+    Ok(out)
+}
+
+fn sarif(summary: &Summary<'_>) -> serde_json::Value {
+    use serde_json::json;
+
+    let results: Vec<_> = summary
+        .crates
+        .iter()
+        .flat_map(|krate| {
+            krate.findings.iter().map(move |finding| {
+                (
+                    format!("{} ({} {})", finding.message, krate.name, krate.version),
+                    finding,
+                )
+            })
+        })
+        .chain(
+            summary
+                .findings
+                .iter()
+                .map(|finding| (finding.message.to_owned(), finding)),
+        )
+        .map(|(mut text, finding)| {
+            for note in finding.notes {
+                text.push('\n');
+                text.push_str(note);
+            }
+
+            let mut result = json!({
+                "level": match finding.severity {
+                    "bug" | "error" => "error",
+                    "warning" => "warning",
+                    _ => "note",
+                },
+                "message": { "text": text },
+            });
+
+            if let Some(code) = finding.code {
+                result["ruleId"] = json!(code);
+            }
+
+            if let Some(loc) = &finding.location {
+                // Absolute paths need to be file uris, relative paths are
+                // fine as is
+                let uri = url::Url::from_file_path(&loc.path)
+                    .map_or_else(|_| loc.path.clone(), |url| url.to_string());
+
+                result["locations"] = json!([{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": uri },
+                        "region": {
+                            "startLine": loc.line,
+                            "startColumn": loc.column,
+                        },
+                    },
+                }]);
+            }
+
+            result
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+pub(super) fn render(
+    format: SummaryFormat,
+    checked: &[Checked<'_>],
+    unattributed: &[Diagnostic],
+    files: &Files,
+) -> anyhow::Result<String> {
+    let summary = summarize(checked, unattributed, files);
+
+    Ok(match format {
+        SummaryFormat::Json => serde_json::to_string_pretty(&summary)?,
+        SummaryFormat::Junit => junit(&summary)?,
+        SummaryFormat::Sarif => serde_json::to_string_pretty(&sarif(&summary))?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generate::test::Workspace;
+    use codespan_reporting::diagnostic::Label;
+
+    struct Fixture {
+        ws: Workspace,
+        files: Files,
+        diagnostics: Vec<Vec<Diagnostic>>,
+        unattributed: Vec<Diagnostic>,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let ws = Workspace::new(name);
+
+            let mut files = Files::new();
+            let manifest = files.add(
+                "app/Cargo.toml",
+                "[package]\nlicense = \"GPL-3.0\"\n".to_owned(),
+            );
+
+            let diagnostics = vec![
+                vec![Diagnostic::error()
+                    .with_code("rejected")
+                    .with_message("failed to satisfy license requirements <GPL-3.0>")
+                    .with_labels(vec![Label::primary(manifest, 10..27)])
+                    .with_notes(vec!["GPL-3.0 is not accepted".to_owned()])],
+                vec![Diagnostic::warning().with_message("no license files were found")],
+            ];
+
+            let unattributed = vec![Diagnostic::warning()
+                .with_message("unable to retrieve definition for crate 'gone 1.0.0'")];
+
+            Self {
+                ws,
+                files,
+                diagnostics,
+                unattributed,
+            }
+        }
+
+        fn render(&self, format: SummaryFormat) -> String {
+            let checked: Vec<_> = self
+                .ws
+                .krates
+                .krates()
+                .zip(&self.diagnostics)
+                .map(|(kn, diagnostics)| Checked {
+                    krate: &kn.krate,
+                    licenses: Vec::new(),
+                    diagnostics: diagnostics.clone(),
+                })
+                .collect();
+
+            render(format, &checked, &self.unattributed, &self.files).unwrap()
+        }
+    }
+
+    #[test]
+    fn renders_json() {
+        let fixture = Fixture::new("summary-json");
+        let summary: serde_json::Value =
+            serde_json::from_str(&fixture.render(SummaryFormat::Json)).unwrap();
+
+        assert_eq!(summary["version"], SCHEMA_VERSION);
+        // The totals include findings that aren't attributed to a crate, so
+        // they agree with the exit code
+        assert_eq!(summary["errors"], 1);
+        assert_eq!(summary["warnings"], 2);
+
+        let app = &summary["crates"][0];
+        assert_eq!(app["name"], "app");
+        assert_eq!(app["findings"][0]["severity"], "error");
+        assert_eq!(app["findings"][0]["code"], "rejected");
+        assert_eq!(
+            app["findings"][0]["location"],
+            serde_json::json!({ "path": "app/Cargo.toml", "line": 2, "column": 1 })
+        );
+
+        let dep = &summary["crates"][1];
+        assert_eq!(dep["findings"][0]["severity"], "warning");
+        assert!(dep["findings"][0]["location"].is_null());
+
+        assert_eq!(
+            summary["findings"][0]["message"],
+            "unable to retrieve definition for crate 'gone 1.0.0'"
+        );
+    }
+
+    #[test]
+    fn renders_junit() {
+        let fixture = Fixture::new("summary-junit");
+        let junit = fixture.render(SummaryFormat::Junit);
+
+        assert!(junit.contains(r#"<testsuites name="cargo-about" tests="3" failures="1">"#));
+        assert!(junit.contains(r#"<testcase classname="licenses" name="app 0.1.0">"#));
+        assert!(junit.contains(
+            r#"<failure type="error" message="failed to satisfy license requirements &lt;GPL-3.0&gt;">"#
+        ));
+        assert!(
+            junit.contains("  --&gt; app/Cargo.toml:2:1\n  = GPL-3.0 is not accepted</failure>")
+        );
+        assert!(junit.contains("<system-out>warning: no license files were found</system-out>"));
+        assert!(junit.contains(r#"<testcase classname="licenses" name="cargo-about">"#));
+        assert!(junit.contains(
+            "<system-out>warning: unable to retrieve definition for crate &apos;gone 1.0.0&apos;</system-out>"
+        ));
+        assert!(!junit.contains("<GPL-3.0>"));
+    }
+
+    #[test]
+    fn renders_sarif() {
+        let fixture = Fixture::new("summary-sarif");
+        let sarif: serde_json::Value =
+            serde_json::from_str(&fixture.render(SummaryFormat::Sarif)).unwrap();
+
+        assert_eq!(sarif["version"], "2.1.0");
+
+        let results = sarif["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 3);
+
+        assert_eq!(results[0]["level"], "error");
+        assert_eq!(results[0]["ruleId"], "rejected");
+        assert_eq!(
+            results[0]["message"]["text"],
+            "failed to satisfy license requirements <GPL-3.0> (app 0.1.0)\nGPL-3.0 is not accepted"
+        );
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"],
+            serde_json::json!({
+                "artifactLocation": { "uri": "app/Cargo.toml" },
+                "region": { "startLine": 2, "startColumn": 1 },
+            })
+        );
+
+        assert_eq!(results[1]["level"], "warning");
+        assert!(results[1].get("locations").is_none());
+        assert_eq!(
+            results[2]["message"]["text"],
+            "unable to retrieve definition for crate 'gone 1.0.0'"
+        );
+    }
+}
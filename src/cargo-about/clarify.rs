@@ -1,5 +1,8 @@
 use anyhow::Context as _;
-use cargo_about::licenses::fetch::GitCache;
+use cargo_about::licenses::{
+    config::{Clarification, ClarificationFile},
+    fetch::GitCache,
+};
 use krates::Utf8PathBuf as PathBuf;
 
 fn parse_subsection(s: &str) -> anyhow::Result<(Option<String>, Option<String>)> {
@@ -36,6 +39,15 @@ pub enum Subcommand {
         /// The crate's `<name>-<version>` spec to retrieve. The crate source must already be downloaded.
         spec: String,
     },
+    /// Detects the license files of a crate in the crate graph and writes a
+    /// clarification for them into the config, merging with any existing
+    /// clarification for the crate
+    Write {
+        #[clap(flatten)]
+        krates: crate::common::KrateArgs,
+        /// The crate to clarify, as `<name>` or `<name>@<version>`
+        spec: String,
+    },
 }
 
 #[derive(clap::Parser, Debug)]
@@ -47,23 +59,37 @@ pub struct Args {
     /// The minimum confidence score a license must have
     #[clap(long, default_value = "0.8")]
     threshold: f32,
-    /// The relative file path from the root of the source
-    path: PathBuf,
+    /// The relative file path from the root of the source. Required by every
+    /// subcommand except `write`
+    path: Option<PathBuf>,
     #[clap(subcommand)]
     cmd: Subcommand,
 }
 
 pub fn cmd(args: Args) -> anyhow::Result<()> {
+    if let Subcommand::Write { krates, spec } = &args.cmd {
+        anyhow::ensure!(
+            args.path.is_none() && args.subsections.is_empty(),
+            "`write` detects the license files of the crate itself, a path or subsections can't be specified"
+        );
+
+        return write(krates, spec, args.threshold);
+    }
+
+    let path = args
+        .path
+        .context("the relative path of the file to clarify must be specified")?;
+
     let contents = match args.cmd {
         Subcommand::Path { root } => {
-            let full_path = root.join(&args.path);
+            let full_path = root.join(&path);
             std::fs::read_to_string(&full_path)
                 .with_context(|| format!("unable to read file '{}'", full_path))?
         }
         Subcommand::Repo { rev, repo } => {
             let gc = GitCache::default();
 
-            gc.retrieve_remote(repo.as_str(), &rev, &args.path)
+            gc.retrieve_remote(repo.as_str(), &rev, &path)
                 .context("failed to retrieve remote file")?
         }
         Subcommand::Crate { spec } => {
@@ -99,9 +125,10 @@ pub fn cmd(args: Args) -> anyhow::Result<()> {
             let vcs_info = GitCache::parse_vcs_info(&crate_path.join(".cargo_vcs_info.json"))
                 .context("failed to read sha1")?;
 
-            gc.retrieve_remote(&pkg.package.repository, &vcs_info.git.sha1, &args.path)
+            gc.retrieve_remote(&pkg.package.repository, &vcs_info.git.sha1, &path)
                 .context("failed to retrieve remote file")?
         }
+        Subcommand::Write { .. } => unreachable!(),
     };
 
     let subsections = if args.subsections.is_empty() {
@@ -135,25 +162,21 @@ pub fn cmd(args: Args) -> anyhow::Result<()> {
     };
 
     if contents.contains('\r') {
-        log::warn!("{} contains CRLF line endings, the checksums will be calculated with normal LF line endings to match checksum verification", args.path);
+        log::warn!("{} contains CRLF line endings, the checksums will be calculated with normal LF line endings to match checksum verification", path);
     }
 
     let license_store = cargo_about::licenses::store_from_cache()?;
 
     let strategy = askalono::ScanStrategy::new(&license_store)
         .mode(askalono::ScanMode::Elimination)
-        .confidence_threshold(
-            std::cmp::max(10, std::cmp::min(100, (args.threshold * 100.0) as u32)) as f32 / 100.0,
-        )
+        .confidence_threshold(((args.threshold * 100.0) as u32).clamp(10, 100) as f32 / 100.0)
         .optimize(false)
         .max_passes(1);
 
     let mut final_expression = String::new();
     let mut files = Vec::with_capacity(subsections.len());
 
-    use cargo_about::licenses::config::{Clarification, ClarificationFile};
-
-    let file_name = args.path.file_name().unwrap().to_owned();
+    let file_name = path.file_name().unwrap().to_owned();
 
     for (ind, (subrange, (start, end))) in subsections.into_iter().enumerate() {
         let subsection = &contents[subrange];
@@ -238,3 +261,593 @@ pub fn cmd(args: Args) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Computes the SHA-256 checksum of the text in hex
+fn checksum(text: &str) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, text.as_bytes());
+    cargo_about::to_hex(digest.as_ref())
+}
+
+/// Finds the start and end text that identify a subsection of the contents,
+/// matching them the same way they are matched when a clarification is
+/// validated. Bounds at the very start or end of the contents are `None`
+fn subsection_bounds(
+    contents: &str,
+    range: std::ops::Range<usize>,
+) -> Option<(Option<String>, Option<String>)> {
+    let text = contents.get(range.clone())?;
+    let lines: Vec<_> = text.split_inclusive('\n').collect();
+
+    let start = if range.start == 0 {
+        None
+    } else {
+        let mut len = 0;
+        let start = lines.iter().find_map(|line| {
+            len += line.len();
+            let start = text[..len].trim_end();
+            (!start.is_empty() && contents.find(start) == Some(range.start)).then(|| start)
+        })?;
+
+        Some(start.to_owned())
+    };
+
+    let end = if range.end == contents.len() {
+        None
+    } else {
+        let mut len = 0;
+        let end = lines.iter().rev().find_map(|line| {
+            len += line.len();
+            let end = &text[text.len() - len..];
+            (!end.trim().is_empty()
+                && contents[range.start..]
+                    .find(end)
+                    .map(|i| range.start + i + end.len())
+                    == Some(range.end))
+            .then(|| end)
+        })?;
+
+        Some(end.to_owned())
+    };
+
+    Some((start, end))
+}
+
+/// Combines the licenses of each file into the overall expression for the
+/// crate
+fn overall_expression<'e>(
+    licenses: impl Iterator<Item = &'e spdx::Expression>,
+) -> anyhow::Result<spdx::Expression> {
+    let mut parts = Vec::<String>::new();
+
+    for license in licenses {
+        let part = if license.requirements().count() > 1 {
+            format!("({})", license.as_ref())
+        } else {
+            license.as_ref().to_owned()
+        };
+
+        // Some license files (read: ring) can have duplicates of the same
+        // license but with different copyright holders
+        if !parts.contains(&part) {
+            parts.push(part);
+        }
+    }
+
+    let expression = parts.join(" AND ");
+    spdx::Expression::parse(&expression).map_err(|e| {
+        anyhow::anyhow!(
+            "failed to parse '{}' as the total expression for all of the licenses: {}",
+            expression,
+            e,
+        )
+    })
+}
+
+/// Finds the entry in the config for the crate, preferring one that already
+/// has a clarification, returning its key, and its index if the key is an
+/// array of tables
+fn find_entry(
+    doc: &toml_edit::Document,
+    name: &str,
+    version: &krates::semver::Version,
+) -> Option<(String, Option<usize>)> {
+    let matches_version = |req: Option<&str>| {
+        req.map_or(true, |req| {
+            krates::semver::VersionReq::parse(req).map_or(false, |req| req.matches(version))
+        })
+    };
+
+    let mut candidates = Vec::new();
+
+    for (key, item) in doc.iter() {
+        let (krate_name, req) = match key.split_once('@') {
+            Some((krate_name, req)) => (krate_name, Some(req)),
+            None => (key, None),
+        };
+
+        if krate_name != name || !matches_version(req) {
+            continue;
+        }
+
+        if let Some(aot) = item.as_array_of_tables() {
+            for (i, table) in aot.iter().enumerate() {
+                if matches_version(table.get("version").and_then(toml_edit::Item::as_str)) {
+                    candidates.push((key.to_owned(), Some(i), table.contains_key("clarify")));
+                }
+            }
+        } else if let Some(table) = item.as_table_like() {
+            if matches_version(table.get("version").and_then(toml_edit::Item::as_str)) {
+                candidates.push((key.to_owned(), None, table.contains_key("clarify")));
+            }
+        }
+    }
+
+    candidates
+        .iter()
+        .find(|(_, _, has_clarify)| *has_clarify)
+        .or_else(|| candidates.first())
+        .map(|(key, index, _)| (key.clone(), *index))
+}
+
+/// Gets the table for the key, inserting an empty one if it doesn't exist,
+/// along with whether it is an inline table
+fn child_table<'t>(
+    parent: &'t mut dyn toml_edit::TableLike,
+    key: &str,
+    inline: bool,
+) -> anyhow::Result<(&'t mut dyn toml_edit::TableLike, bool)> {
+    if !parent.contains_key(key) {
+        parent.insert(
+            key,
+            if inline {
+                toml_edit::value(toml_edit::InlineTable::new())
+            } else {
+                toml_edit::table()
+            },
+        );
+    }
+
+    let item = parent.get_mut(key).unwrap();
+    let inline = item.is_inline_table();
+    let table = item
+        .as_table_like_mut()
+        .with_context(|| format!("`{}` is not a table", key))?;
+
+    Ok((table, inline))
+}
+
+fn is_same_file(table: &dyn toml_edit::TableLike, file: &ClarificationFile) -> bool {
+    table.get("path").and_then(toml_edit::Item::as_str) == Some(file.path.as_str())
+        && table.get("start").and_then(toml_edit::Item::as_str) == file.start.as_deref()
+}
+
+/// Sets the checksum and bounds of the file, keeping any license that was
+/// already specified for it
+fn set_file(table: &mut dyn toml_edit::TableLike, file: &ClarificationFile) {
+    table.insert("path", toml_edit::value(file.path.as_str()));
+
+    if let Some(license) = &file.license {
+        if !table.contains_key("license") {
+            table.insert("license", toml_edit::value(license.as_ref()));
+        }
+    }
+
+    table.insert("checksum", toml_edit::value(file.checksum.as_str()));
+
+    for (key, bound) in [("start", &file.start), ("end", &file.end)] {
+        match bound {
+            Some(bound) => {
+                table.insert(key, toml_edit::value(bound.as_str()));
+            }
+            None => {
+                table.remove(key);
+            }
+        }
+    }
+}
+
+/// Merges the clarification into the config for the crate, creating the
+/// entry for the crate if there isn't one. The license of an existing
+/// clarification is kept, files are matched by their path and start, and
+/// everything else in the config, including comments, is left untouched
+fn merge_clarification(
+    doc: &mut toml_edit::Document,
+    name: &str,
+    version: &krates::semver::Version,
+    clarification: &Clarification,
+) -> anyhow::Result<()> {
+    use toml_edit::{Item, Value};
+
+    let (key, index) = match find_entry(doc, name, version) {
+        Some(entry) => entry,
+        None => {
+            // Scope the new entry to the exact version if there are already
+            // entries for other versions of the crate, so they aren't affected
+            let key = if doc
+                .iter()
+                .any(|(key, _)| key.split_once('@').map_or(key, |(name, _)| name) == name)
+            {
+                format!("{}@={}", name, version)
+            } else {
+                name.to_owned()
+            };
+
+            let mut table = toml_edit::Table::new();
+            table.set_implicit(true);
+            doc.insert(&key, Item::Table(table));
+
+            (key, None)
+        }
+    };
+
+    let item = &mut doc[&key];
+    let inline = item.is_inline_table();
+    let entry = match index {
+        Some(i) => item
+            .as_array_of_tables_mut()
+            .and_then(|aot| aot.get_mut(i))
+            .map(|table| table as &mut dyn toml_edit::TableLike),
+        None => item.as_table_like_mut(),
+    }
+    .with_context(|| format!("`{}` is not a table", key))?;
+
+    let (clarify, inline) = child_table(entry, "clarify", inline)?;
+
+    let license = clarification.license.as_ref();
+    match clarify.get("license").and_then(Item::as_str) {
+        Some(existing) if existing != license => {
+            log::warn!(
+                "keeping the existing license '{}' for '{}', the license files that were detected are '{}'",
+                existing,
+                key,
+                license
+            );
+        }
+        Some(_) => {}
+        None => {
+            clarify.insert("license", toml_edit::value(license));
+        }
+    }
+
+    // Prefer `[[<crate>.clarify.files]]` unless the clarification is inline,
+    // or the user has already added files inline
+    let replace_files = match clarify.get("files") {
+        None => true,
+        Some(files) => !inline && files.as_array().map_or(false, |files| files.is_empty()),
+    };
+
+    if replace_files {
+        clarify.insert(
+            "files",
+            if inline {
+                toml_edit::value(toml_edit::Array::new())
+            } else {
+                Item::ArrayOfTables(toml_edit::ArrayOfTables::new())
+            },
+        );
+    }
+
+    let files = clarify.get_mut("files").unwrap();
+
+    for file in &clarification.files {
+        let existing = match files {
+            Item::ArrayOfTables(aot) => aot
+                .iter_mut()
+                .map(|table| table as &mut dyn toml_edit::TableLike)
+                .find(|table| is_same_file(*table, file)),
+            Item::Value(Value::Array(array)) => array
+                .iter_mut()
+                .filter_map(Value::as_inline_table_mut)
+                .map(|table| table as &mut dyn toml_edit::TableLike)
+                .find(|table| is_same_file(*table, file)),
+            _ => anyhow::bail!("`{}.clarify.files` is not an array", key),
+        };
+
+        match existing {
+            Some(table) => set_file(table, file),
+            None => match files {
+                Item::ArrayOfTables(aot) => {
+                    let mut table = toml_edit::Table::new();
+                    set_file(&mut table, file);
+                    aot.push(table);
+                }
+                Item::Value(Value::Array(array)) => {
+                    let mut table = toml_edit::InlineTable::new();
+                    set_file(&mut table, file);
+                    array.push(table);
+                }
+                _ => unreachable!(),
+            },
+        }
+    }
+
+    Ok(())
+}
+
+fn write(krate_args: &crate::common::KrateArgs, spec: &str, threshold: f32) -> anyhow::Result<()> {
+    use cargo_about::licenses::{self, LicenseFileKind};
+
+    let (name, version) = crate::common::parse_spec(spec)?;
+
+    let manifest_path = krate_args.manifest_path()?;
+    let cfg = krate_args.config(&manifest_path)?;
+    let krates = krate_args.krates(&manifest_path, &cfg)?;
+
+    let mut matching = krates
+        .krates()
+        .map(|kn| &kn.krate)
+        .filter(|krate| crate::common::matches_spec(krate, name, version.as_ref()));
+
+    let krate = match (matching.next(), matching.next()) {
+        (Some(krate), None) => krate,
+        (None, _) => anyhow::bail!(
+            "unable to find a crate matching '{}' in the crate graph",
+            spec
+        ),
+        (Some(_), Some(_)) => anyhow::bail!(
+            "multiple versions of '{0}' are in the crate graph, specify one with `{0}@<version>`",
+            name
+        ),
+    };
+
+    log::info!("loading license store");
+    let store = licenses::store_from_cache()?;
+
+    let license_files =
+        licenses::Gatherer::with_store(std::sync::Arc::new(store), cd::client::Client::new())
+            .with_confidence_threshold(threshold)
+            .scan(krate, &cfg);
+
+    let root = krate.manifest_path.parent().unwrap();
+    let mut files = Vec::new();
+
+    for lf in license_files {
+        // Headers are part of source files which change far more often than
+        // their license, so only license texts are used as the source of truth
+        let text = match &lf.kind {
+            LicenseFileKind::Text(text) => text,
+            LicenseFileKind::AddendumText(..) | LicenseFileKind::Header => continue,
+        };
+
+        let path = lf
+            .path
+            .strip_prefix(root)
+            .with_context(|| format!("'{}' is not within '{}'", lf.path, root))?
+            .to_owned();
+
+        let (start, end) = match &lf.range {
+            Some(range) => {
+                let contents = std::fs::read_to_string(&lf.path)
+                    .with_context(|| format!("unable to read file '{}'", lf.path))?;
+
+                subsection_bounds(&contents, range.clone()).with_context(|| {
+                    format!(
+                        "unable to find text that identifies bytes {}..{} of '{}'",
+                        range.start, range.end, path
+                    )
+                })?
+            }
+            None => (None, None),
+        };
+
+        if text.contains('\r') {
+            log::warn!(
+                "{} contains CRLF line endings, the checksum will be calculated with them as is",
+                path
+            );
+        }
+
+        files.push(ClarificationFile {
+            path,
+            checksum: checksum(text),
+            license: Some(lf.license_expr),
+            start,
+            end,
+        });
+    }
+
+    anyhow::ensure!(
+        !files.is_empty(),
+        "unable to detect any license files for '{}'",
+        krate.id
+    );
+
+    let clarification = Clarification {
+        license: overall_expression(files.iter().filter_map(|file| file.license.as_ref()))?,
+        override_git_commit: None,
+        files,
+        git: Vec::new(),
+    };
+
+    let config_path = krate_args
+        .config_path(&manifest_path)
+        .unwrap_or_else(|| manifest_path.with_file_name("about.toml"));
+
+    let contents = if config_path.exists() {
+        std::fs::read_to_string(&config_path)
+            .with_context(|| format!("unable to read {}", config_path))?
+    } else {
+        String::new()
+    };
+
+    let mut doc: toml_edit::Document = contents
+        .parse()
+        .with_context(|| format!("unable to parse {}", config_path))?;
+
+    merge_clarification(&mut doc, &krate.name, &krate.version, &clarification)?;
+
+    std::fs::write(&config_path, doc.to_string())
+        .with_context(|| format!("unable to write {}", config_path))?;
+
+    println!("wrote clarification for {} to {}", krate.id, config_path);
+    for file in &clarification.files {
+        println!(
+            "  {} - {} ({})",
+            file.path,
+            file.license.as_ref().map_or("", |license| license.as_ref()),
+            file.checksum
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_subsection_bounds() {
+        let contents = "MIT\nsame\nfirst\nsame\nISC\nsame\nsecond\nsame\ntrailer\n";
+
+        let first = contents.find("first").unwrap()..contents.find("ISC").unwrap();
+        assert_eq!(
+            subsection_bounds(contents, first),
+            Some((Some("first".to_owned()), Some("same\n".to_owned())))
+        );
+
+        let second = contents.find("ISC").unwrap()..contents.find("trailer").unwrap();
+        assert_eq!(
+            subsection_bounds(contents, second),
+            Some((Some("ISC".to_owned()), Some("second\nsame\n".to_owned())))
+        );
+
+        assert_eq!(
+            subsection_bounds(contents, 0..contents.len()),
+            Some((None, None))
+        );
+    }
+
+    #[test]
+    fn merges_clarifications() {
+        let mut doc: toml_edit::Document = r#"# The licenses we accept
+accepted = ["MIT"]
+
+# ring is special
+[ring]
+accepted = ["OpenSSL"]
+
+[ring.clarify]
+license = "ISC AND MIT AND OpenSSL"
+
+[[ring.clarify.files]]
+path = "LICENSE"
+license = "OpenSSL" # pinned by hand
+start = "OpenSSL License"
+checksum = "00"
+
+[[ring.clarify.files]]
+path = "src/other.txt"
+checksum = "11"
+
+[other]
+accepted = ["ISC"]
+"#
+        .parse()
+        .unwrap();
+
+        let file =
+            |path: &str, license: &str, checksum: &str, start: Option<&str>| ClarificationFile {
+                path: path.into(),
+                checksum: checksum.to_owned(),
+                license: Some(spdx::Expression::parse(license).unwrap()),
+                start: start.map(String::from),
+                end: None,
+            };
+
+        let clarification = Clarification {
+            license: spdx::Expression::parse("OpenSSL AND ISC").unwrap(),
+            override_git_commit: None,
+            files: vec![
+                file("LICENSE", "OpenSSL", "aa", Some("OpenSSL License")),
+                file("LICENSE", "ISC", "bb", Some("Copyright 2015-2016")),
+            ],
+            git: Vec::new(),
+        };
+
+        let version = krates::semver::Version::new(0, 16, 20);
+        merge_clarification(&mut doc, "ring", &version, &clarification).unwrap();
+        merge_clarification(&mut doc, "new", &version, &clarification).unwrap();
+
+        assert_eq!(
+            doc.to_string(),
+            r#"# The licenses we accept
+accepted = ["MIT"]
+
+# ring is special
+[ring]
+accepted = ["OpenSSL"]
+
+[ring.clarify]
+license = "ISC AND MIT AND OpenSSL"
+
+[[ring.clarify.files]]
+path = "LICENSE"
+license = "OpenSSL" # pinned by hand
+start = "OpenSSL License"
+checksum = "aa"
+
+[[ring.clarify.files]]
+path = "src/other.txt"
+checksum = "11"
+
+[[ring.clarify.files]]
+path = "LICENSE"
+license = "ISC"
+checksum = "bb"
+start = "Copyright 2015-2016"
+
+[other]
+accepted = ["ISC"]
+
+[new.clarify]
+license = "OpenSSL AND ISC"
+
+[[new.clarify.files]]
+path = "LICENSE"
+license = "OpenSSL"
+checksum = "aa"
+start = "OpenSSL License"
+
+[[new.clarify.files]]
+path = "LICENSE"
+license = "ISC"
+checksum = "bb"
+start = "Copyright 2015-2016"
+"#
+        );
+    }
+
+    #[test]
+    fn scopes_new_entries_to_version() {
+        let mut doc: toml_edit::Document = "[\"ring@0.16\"]\naccepted = [\"OpenSSL\"]\n"
+            .parse()
+            .unwrap();
+
+        let clarification = Clarification {
+            license: spdx::Expression::parse("ISC").unwrap(),
+            override_git_commit: None,
+            files: vec![ClarificationFile {
+                path: "LICENSE".into(),
+                checksum: "aa".to_owned(),
+                license: None,
+                start: None,
+                end: None,
+            }],
+            git: Vec::new(),
+        };
+
+        merge_clarification(
+            &mut doc,
+            "ring",
+            &krates::semver::Version::new(0, 17, 0),
+            &clarification,
+        )
+        .unwrap();
+
+        assert_eq!(
+            doc.to_string(),
+            "[\"ring@0.16\"]\naccepted = [\"OpenSSL\"]\n\n[\"ring@=0.17.0\".clarify]\nlicense = \"ISC\"\n\n[[\"ring@=0.17.0\".clarify.files]]\npath = \"LICENSE\"\nchecksum = \"aa\"\n"
+        );
+    }
+}
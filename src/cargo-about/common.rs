@@ -0,0 +1,271 @@
+use anyhow::{bail, Context as _};
+use cargo_about::licenses::{
+    config::Config,
+    fetch::{DeniedFetch, Network, NetworkMode},
+};
+use codespan_reporting::{diagnostic::Diagnostic, term};
+use krates::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+
+/// Options for selecting the crate graph to operate on, shared by every
+/// subcommand that needs to gather crates
+#[derive(clap::Args, Debug)]
+pub struct KrateArgs {
+    /// Path to the config to use
+    ///
+    /// Defaults to `<manifest_root>/about.toml` if not specified
+    #[clap(short, long)]
+    pub config: Option<PathBuf>,
+    /// Space-separated list of features to activate
+    #[clap(long)]
+    pub features: Vec<String>,
+    /// Activate all available features
+    #[clap(long)]
+    pub all_features: bool,
+    /// Do not activate the `default` feature
+    #[clap(long)]
+    pub no_default_features: bool,
+    /// The path of the Cargo.toml for the root crate, defaults to the
+    /// current crate or workspace in the current working directory
+    #[clap(short, long)]
+    pub manifest_path: Option<PathBuf>,
+    /// Scan licenses for the entire workspace, not just the active package
+    #[clap(long)]
+    pub workspace: bool,
+    /// Never access the network, only data that has already been cached
+    /// will be used
+    #[clap(long)]
+    pub offline: bool,
+    /// The same as `--offline`, but it is an error if any data would have
+    /// needed to be retrieved from the network, and the Cargo.lock must be
+    /// up to date
+    #[clap(long)]
+    pub frozen: bool,
+}
+
+impl KrateArgs {
+    /// Gets the path to the root manifest, defaulting to the Cargo.toml in
+    /// the current working directory
+    pub fn manifest_path(&self) -> anyhow::Result<PathBuf> {
+        let manifest_path = match self.manifest_path.clone() {
+            Some(mp) => mp,
+            None => {
+                let cwd = std::env::current_dir()
+                    .context("unable to determine current working directory")?;
+                let mut cwd = PathBuf::from_path_buf(cwd).map_err(|pb| {
+                    anyhow::anyhow!(
+                        "current working directory '{}' is not a utf-8 path",
+                        pb.display()
+                    )
+                })?;
+
+                cwd.push("Cargo.toml");
+                cwd
+            }
+        };
+
+        if !manifest_path.exists() {
+            bail!("cargo manifest path '{}' does not exist", manifest_path);
+        }
+
+        Ok(manifest_path)
+    }
+
+    /// Loads the config, either from the path specified by the user, or by
+    /// searching for an `about.toml` relative to the manifest
+    pub fn config(&self, manifest_path: &Path) -> anyhow::Result<Config> {
+        match &self.config {
+            Some(cfg_path) => {
+                let cfg_str = std::fs::read_to_string(cfg_path)
+                    .with_context(|| format!("unable to read {}", cfg_path))?;
+                toml::from_str(&cfg_str)
+                    .with_context(|| format!("unable to deserialize config from {}", cfg_path))
+            }
+            None => load_config(manifest_path),
+        }
+    }
+
+    /// Gets the path of the config, either the path specified by the user,
+    /// or the `about.toml` found relative to the manifest, if there is one
+    pub fn config_path(&self, manifest_path: &Path) -> Option<PathBuf> {
+        self.config.clone().or_else(|| find_config(manifest_path))
+    }
+
+    /// Gets the network mode specified by the user
+    pub fn network_mode(&self) -> NetworkMode {
+        if self.frozen {
+            NetworkMode::Frozen
+        } else if self.offline {
+            NetworkMode::Offline
+        } else {
+            NetworkMode::Online
+        }
+    }
+
+    /// Gathers the crate graph for the manifest
+    pub fn krates(
+        &self,
+        manifest_path: &Path,
+        cfg: &Config,
+    ) -> anyhow::Result<cargo_about::Krates> {
+        log::info!("gathering crates for {}", manifest_path);
+        cargo_about::get_all_crates(
+            manifest_path,
+            self.no_default_features,
+            self.all_features,
+            self.features.clone(),
+            self.workspace,
+            self.network_mode(),
+            cfg,
+        )
+    }
+}
+
+/// Creates the stream diagnostics are emitted to
+pub fn diagnostic_stream(color: crate::Color) -> term::termcolor::StandardStream {
+    use term::termcolor::ColorChoice;
+
+    term::termcolor::StandardStream::stderr(match color {
+        crate::Color::Auto => {
+            // The termcolor crate doesn't check the stream to see if it's a TTY
+            // which doesn't really fit with how the rest of the coloring works
+            if atty::is(atty::Stream::Stderr) {
+                ColorChoice::Auto
+            } else {
+                ColorChoice::Never
+            }
+        }
+        crate::Color::Always => ColorChoice::Always,
+        crate::Color::Never => ColorChoice::Never,
+    })
+}
+
+/// Creates a diagnostic for a remote retrieval that was denied because the
+/// network was disabled. These are warnings when offline, but errors when frozen.
+pub fn denied_diagnostic(denied: &DeniedFetch, mode: NetworkMode) -> Diagnostic<codespan::FileId> {
+    if mode == NetworkMode::Frozen {
+        Diagnostic::error()
+    } else {
+        Diagnostic::warning()
+    }
+    .with_message(format!(
+        "unable to retrieve {} for crate '{}'",
+        denied.what, denied.krate
+    ))
+    .with_notes(vec![
+        "network access is disabled and the data is not present in the cache".to_owned(),
+    ])
+}
+
+/// Reports every remote retrieval that was denied because the network was
+/// disabled, failing if the network mode was frozen
+pub fn report_denied(
+    network: &Network,
+    stream: &term::termcolor::StandardStream,
+) -> anyhow::Result<()> {
+    let denied = network.take_denied();
+    if denied.is_empty() {
+        return Ok(());
+    }
+
+    let files = cargo_about::licenses::resolution::Files::new();
+    let diag_cfg = term::Config::default();
+    let mut streaml = stream.lock();
+
+    for denied in &denied {
+        let diag = denied_diagnostic(denied, network.mode());
+        term::emit(&mut streaml, &diag_cfg, &files, &diag)?;
+    }
+
+    if network.mode() == NetworkMode::Frozen {
+        bail!(
+            "{} remote retrievals were required while frozen, run `cargo about cache warm` to populate the cache",
+            denied.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Escapes text so that it can be used in XML element content and attribute
+/// values
+pub fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Parses a `<name>[@<version>]` crate spec
+pub fn parse_spec(spec: &str) -> anyhow::Result<(&str, Option<krates::semver::Version>)> {
+    match spec.split_once('@') {
+        Some((name, version)) => Ok((
+            name,
+            Some(
+                krates::semver::Version::parse(version)
+                    .with_context(|| format!("'{}' has an invalid version", spec))?,
+            ),
+        )),
+        None => Ok((spec, None)),
+    }
+}
+
+/// Whether the crate matches a `<name>[@<version>]` spec
+pub fn matches_spec(
+    krate: &cargo_about::Krate,
+    name: &str,
+    version: Option<&krates::semver::Version>,
+) -> bool {
+    krate.name == name && version.map_or(true, |v| krate.version == *v)
+}
+
+/// Finds the `about.toml` for the manifest, moving up directories from the
+/// manifest, to handle cases where eg in a workspace there is a top-level
+/// about.toml but the user is only getting a listing for a particular crate
+/// from it
+fn find_config(manifest_path: &Path) -> Option<PathBuf> {
+    let mut parent = manifest_path.parent();
+
+    while let Some(p) = parent {
+        // We _could_ limit ourselves to only directories that also have a Cargo.toml
+        // in them, but there could be cases where someone has multiple
+        // rust projects in subdirectories with a single top level about.toml that is
+        // used across all of them, we could also introduce a metadata entry for the
+        // relative path of the about.toml to use for the crate/workspace
+
+        // if !p.join("Cargo.toml").exists() {
+        //     parent = p.parent();
+        //     continue;
+        // }
+
+        let about_toml = p.join("about.toml");
+
+        if about_toml.exists() {
+            return Some(about_toml);
+        }
+
+        parent = p.parent();
+    }
+
+    None
+}
+
+fn load_config(manifest_path: &Path) -> anyhow::Result<Config> {
+    if let Some(about_toml) = find_config(manifest_path) {
+        let contents = std::fs::read_to_string(&about_toml)?;
+        let cfg = toml::from_str(&contents)?;
+
+        log::info!("loaded config from {}", about_toml);
+        return Ok(cfg);
+    }
+
+    log::warn!("no 'about.toml' found, falling back to default configuration");
+    Ok(Config::default())
+}
@@ -0,0 +1,235 @@
+use cargo_about::licenses::{self, LicenseFileKind, Provenance};
+use codespan_reporting::term;
+use std::{fmt::Write as _, io::Write as _};
+
+#[derive(clap::Parser, Debug)]
+pub struct Args {
+    #[clap(flatten)]
+    krates: crate::common::KrateArgs,
+    /// The confidence threshold required for license files
+    /// to be positively identified: 0.0 - 1.0
+    #[clap(long, default_value = "0.8")]
+    threshold: f32,
+    /// The crate to explain, as `<name>` or `<name>@<version>`. If a version
+    /// is not specified, every version of the crate in the graph is explained.
+    spec: String,
+}
+
+/// The stages of the gathering pipeline, in the order they are applied
+const STAGES: &[&str] = &[
+    "private",
+    "workarounds",
+    "clarifications",
+    "clearlydefined.io",
+    "file system scan",
+];
+
+fn stage(provenance: &Provenance) -> usize {
+    match provenance {
+        // Only selected crates are explained
+        Provenance::Private | Provenance::Unselected => 0,
+        Provenance::Workaround { .. } => 1,
+        Provenance::Clarification => 2,
+        Provenance::ClearlyDefined { .. } => 3,
+        // Additional files are only ever applied on top of another stage
+        Provenance::FileSystem | Provenance::Additional => 4,
+    }
+}
+
+/// Describes why a stage that was reached didn't determine the license
+fn skipped_reason(
+    stage: usize,
+    krate: &cargo_about::Krate,
+    cfg: &licenses::config::Config,
+) -> String {
+    match stage {
+        0 if !cfg.private.ignore => "private crates are not ignored".to_owned(),
+        0 => "the crate is published".to_owned(),
+        1 if cfg.workarounds.is_empty() => "no workarounds are enabled".to_owned(),
+        1 => format!(
+            "none of the enabled workarounds apply: {}",
+            cfg.workarounds.join(", ")
+        ),
+        2 => match cfg
+            .krate_configs(krate)
+            .filter(|kc| kc.clarify.is_some())
+            .count()
+        {
+            0 => "no clarification is configured".to_owned(),
+            n => format!("{} configured clarification(s) could not be validated", n),
+        },
+        3 if cfg.no_clearly_defined => "disabled by `no-clearly-defined`".to_owned(),
+        3 if !krate
+            .source
+            .as_ref()
+            .map_or(false, |src| src.is_crates_io()) =>
+        {
+            "only used for crates.io crates".to_owned()
+        }
+        3 => "no definition was available".to_owned(),
+        _ => "not applicable".to_owned(),
+    }
+}
+
+fn explain(
+    out: &mut String,
+    kl: &licenses::KrateLicense<'_>,
+    resolved: Option<&licenses::Resolved>,
+    cfg: &licenses::config::Config,
+) -> std::fmt::Result {
+    let krate = kl.krate;
+    let root = krate.manifest_path.parent();
+
+    writeln!(out, "{}", krate.id)?;
+    writeln!(
+        out,
+        "  declared: {}",
+        krate.license.as_deref().unwrap_or("<none>")
+    )?;
+    writeln!(out, "  decided by: {}", kl.provenance)?;
+
+    writeln!(out, "\nprovenance:")?;
+    let decided = stage(&kl.provenance);
+    for (i, name) in STAGES.iter().enumerate() {
+        let status = match i.cmp(&decided) {
+            std::cmp::Ordering::Less => format!("skipped, {}", skipped_reason(i, krate, cfg)),
+            std::cmp::Ordering::Equal => format!("decided by {}", kl.provenance),
+            std::cmp::Ordering::Greater => "not reached".to_owned(),
+        };
+        writeln!(out, "  {}. {}: {}", i + 1, name, status)?;
+    }
+
+    writeln!(out, "\nexpression: {}", kl.lic_info)?;
+
+    writeln!(out, "\nfiles:")?;
+    if kl.license_files.is_empty() {
+        writeln!(out, "  <none>")?;
+    }
+    for lf in &kl.license_files {
+        let path = root
+            .and_then(|root| lf.path.strip_prefix(root).ok())
+            .unwrap_or(&lf.path);
+
+        write!(
+            out,
+            "  {} - {} (confidence {:.2}, {}, from {})",
+            path,
+            lf.license_expr,
+            lf.confidence,
+            match &lf.kind {
+                LicenseFileKind::Text(_) => "text",
+                LicenseFileKind::AddendumText(..) => "addendum",
+                LicenseFileKind::Header => "header",
+            },
+            lf.provenance,
+        )?;
+        if let Some(range) = &lf.range {
+            write!(out, " bytes {}..{}", range.start, range.end)?;
+        }
+        writeln!(out)?;
+    }
+
+    writeln!(out, "\naccepted:")?;
+    writeln!(
+        out,
+        "  global: {}",
+        cfg.accepted
+            .iter()
+            .map(|lic| lic.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )?;
+    let krate_accepted: Vec<_> = cfg
+        .krate_configs(krate)
+        .flat_map(|kc| kc.accepted.iter().map(|lic| lic.to_string()))
+        .collect();
+    if !krate_accepted.is_empty() {
+        writeln!(out, "  crate: {}", krate_accepted.join(", "))?;
+    }
+
+    writeln!(out, "\nrequirements:")?;
+    match resolved {
+        Some(res) if !res.licenses.is_empty() => {
+            for req in &res.licenses {
+                writeln!(out, "  {}", req)?;
+            }
+        }
+        _ => writeln!(out, "  <none>")?,
+    }
+
+    Ok(())
+}
+
+pub fn cmd(args: Args, color: crate::Color) -> anyhow::Result<()> {
+    let (name, version) = crate::common::parse_spec(&args.spec)?;
+
+    let manifest_path = args.krates.manifest_path()?;
+    let cfg = args.krates.config(&manifest_path)?;
+
+    let (krates, store) = rayon::join(
+        || args.krates.krates(&manifest_path, &cfg),
+        || {
+            log::info!("loading license store");
+            licenses::store_from_cache()
+        },
+    );
+
+    let krates = krates?;
+    let store = store?;
+
+    let selection: Vec<_> = krates
+        .krates()
+        .map(|kn| &kn.krate)
+        .filter(|krate| crate::common::matches_spec(krate, name, version.as_ref()))
+        .map(|krate| krate.id.clone())
+        .collect();
+
+    anyhow::ensure!(
+        !selection.is_empty(),
+        "unable to find a crate matching '{}' in the crate graph",
+        args.spec
+    );
+
+    let cache = licenses::cache::Cache::from_config(&cfg.cache)?;
+    let network = licenses::fetch::Network::new(args.krates.network_mode());
+
+    let client = cd::client::Client::new();
+    let gathered = licenses::Gatherer::with_store(std::sync::Arc::new(store), client)
+        .with_confidence_threshold(args.threshold)
+        .with_cache(cache)
+        .with_network(network.clone())
+        .with_selection(selection)
+        .gather(&krates, &cfg);
+
+    let (files, resolved) = licenses::resolution::resolve(&gathered, &cfg);
+
+    let stream = crate::common::diagnostic_stream(color);
+    crate::common::report_denied(&network, &stream)?;
+
+    let diag_cfg = term::Config::default();
+    let stdout = std::io::stdout();
+
+    for (i, (kl, resolved)) in gathered.iter().zip(resolved.iter()).enumerate() {
+        let mut out = String::new();
+        if i > 0 {
+            out.push('\n');
+        }
+
+        explain(&mut out, kl, resolved.as_ref(), &cfg)?;
+
+        {
+            let mut stdout = stdout.lock();
+            stdout.write_all(out.as_bytes())?;
+            stdout.flush()?;
+        }
+
+        if let Some(resolved) = resolved {
+            let mut streaml = stream.lock();
+            for diag in &resolved.diagnostics {
+                term::emit(&mut streaml, &diag_cfg, &files, diag)?;
+            }
+        }
+    }
+
+    Ok(())
+}
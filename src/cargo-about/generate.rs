@@ -8,13 +8,57 @@ use krates::{Utf8Path as Path, Utf8PathBuf as PathBuf};
 use serde::Serialize;
 use std::collections::BTreeMap;
 
+mod cyclonedx;
+mod helpers;
+mod json;
+mod spdx;
+
+/// The format of the generated output
+#[derive(clap::ArgEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Renders one or more handlebars templates
+    Handlebars,
+    /// Serializes a stable, versioned JSON document
+    Json,
+    /// An SPDX 2.3 document in the JSON serialization
+    SpdxJson,
+    /// An SPDX 2.3 document in the tag-value serialization
+    SpdxTag,
+    /// A `CycloneDX` 1.5 bom in the JSON serialization
+    CyclonedxJson,
+    /// A `CycloneDX` 1.5 bom in the XML serialization
+    CyclonedxXml,
+}
+
+/// The roots that separate reports are generated for
+#[derive(clap::ArgEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Per {
+    /// A report for each workspace member
+    Member,
+    /// A report for each binary target of the workspace members, each of
+    /// which can have at most one binary
+    Bin,
+}
+
+impl From<licenses::config::OutputFormat> for Format {
+    fn from(format: licenses::config::OutputFormat) -> Self {
+        use licenses::config::OutputFormat;
+
+        match format {
+            OutputFormat::Handlebars => Self::Handlebars,
+            OutputFormat::Json => Self::Json,
+            OutputFormat::SpdxJson => Self::SpdxJson,
+            OutputFormat::SpdxTag => Self::SpdxTag,
+            OutputFormat::CyclonedxJson => Self::CyclonedxJson,
+            OutputFormat::CyclonedxXml => Self::CyclonedxXml,
+        }
+    }
+}
+
 #[derive(clap::Parser, Debug)]
 pub struct Args {
-    /// Path to the config to use
-    ///
-    /// Defaults to <manifest_root>/about.toml if not specified
-    #[clap(short, long)]
-    config: Option<PathBuf>,
+    #[clap(flatten)]
+    krates: crate::common::KrateArgs,
     /// The confidence threshold required for license files
     /// to be positively identified: 0.0 - 1.0
     #[clap(long, default_value = "0.8")]
@@ -26,208 +70,488 @@ pub struct Args {
     /// A file to write the generated output to.  Typically an .html file.
     #[clap(short, long)]
     output_file: Option<PathBuf>,
-    /// Space-separated list of features to activate
-    #[clap(long)]
-    features: Vec<String>,
-    /// Activate all available features
-    #[clap(long)]
-    all_features: bool,
-    /// Do not activate the `default` feature
-    #[clap(long)]
-    no_default_features: bool,
-    /// The path of the Cargo.toml for the root crate, defaults to the
-    /// current crate or workspace in the current working directory
-    #[clap(short, long)]
-    manifest_path: Option<PathBuf>,
-    /// Scan licenses for the entire workspace, not just the active package
-    #[clap(long)]
-    workspace: bool,
+    /// The format of the generated output, defaults to `handlebars`
+    #[clap(arg_enum, long, ignore_case = true)]
+    format: Option<Format>,
+    /// Generates a separate report for each workspace member or binary,
+    /// containing only the crates it depends on. Each report is written to
+    /// the output file with the name of the member or binary appended to its
+    /// file stem, eg. `licenses-<name>.html`
+    #[clap(arg_enum, long, ignore_case = true)]
+    per: Option<Per>,
     /// The template(s) or template directory to use. Must either be a `.hbs`
-    /// file, or have at least one `.hbs` file in it if it is a directory
-    templates: PathBuf,
+    /// file, or have at least one `.hbs` file in it if it is a directory.
+    /// Required if the format is `handlebars`, unless the outputs are
+    /// configured with `[[output]]` in the config.
+    templates: Option<PathBuf>,
 }
 
-fn load_config(manifest_path: &Path) -> anyhow::Result<cargo_about::licenses::config::Config> {
-    let mut parent = manifest_path.parent();
+pub fn cmd(args: Args, color: crate::Color) -> anyhow::Result<()> {
+    let manifest_path = args.krates.manifest_path()?;
+    let cfg = args.krates.config(&manifest_path)?;
 
-    // Move up directories until we find an about.toml, to handle
-    // cases where eg in a workspace there is a top-level about.toml
-    // but the user is only getting a listing for a particular crate from it
-    while let Some(p) = parent {
-        // We _could_ limit ourselves to only directories that also have a Cargo.toml
-        // in them, but there could be cases where someone has multiple
-        // rust projects in subdirectories with a single top level about.toml that is
-        // used across all of them, we could also introduce a metadata entry for the
-        // relative path of the about.toml to use for the crate/workspace
+    let (all_crates, store) = rayon::join(
+        || args.krates.krates(&manifest_path, &cfg),
+        || {
+            log::info!("loading license store");
+            cargo_about::licenses::store_from_cache()
+        },
+    );
 
-        // if !p.join("Cargo.toml").exists() {
-        //     parent = p.parent();
-        //     continue;
-        // }
+    let krates = all_crates?;
+    let store = store?;
 
-        let about_toml = p.join("about.toml");
+    log::info!("gathered {} crates", krates.len());
 
-        if about_toml.exists() {
-            let contents = std::fs::read_to_string(&about_toml)?;
-            let cfg = toml::from_str(&contents)?;
+    let outputs = outputs(&args, &cfg, &manifest_path)?;
 
-            log::info!("loaded config from {}", about_toml);
-            return Ok(cfg);
+    anyhow::ensure!(
+        args.per.is_none() || outputs.iter().all(|output| output.path.is_some()),
+        "an output file must be specified when generating a report per {}",
+        match args.per {
+            Some(Per::Bin) => "binary",
+            _ => "workspace member",
         }
+    );
+
+    let reports = reports(&krates, args.per)?;
+
+    let cache = licenses::cache::Cache::from_config(&cfg.cache)?;
+    let network = licenses::fetch::Network::new(args.krates.network_mode());
+    if !network.is_online() && cache.is_none() {
+        log::warn!(
+            "running offline with the cache disabled, no remote license information will be used"
+        );
+    }
 
-        parent = p.parent();
+    let client = cd::client::Client::new();
+    let summary = licenses::Gatherer::with_store(std::sync::Arc::new(store), client)
+        .with_confidence_threshold(args.threshold)
+        .with_cache(cache)
+        .with_network(network.clone())
+        .gather(&krates, &cfg);
+
+    let stream = crate::common::diagnostic_stream(color);
+    crate::common::report_denied(&network, &stream)?;
+
+    let (files, resolved) = licenses::resolution::resolve(&summary, &cfg);
+
+    emit_diagnostics(&resolved, &files, stream)?;
+
+    for report in &reports {
+        let paths = cargo_about::DependencyPaths::from_roots(&krates, &report.roots);
+
+        let subset;
+        let (summary, resolved) = match &report.name {
+            None => (summary.as_slice(), resolved.as_slice()),
+            Some(name) => {
+                log::info!("generating report for '{}'", name);
+
+                subset = summary
+                    .iter()
+                    .zip(resolved.iter())
+                    .filter(|(kl, _)| paths.contains(&kl.krate.id))
+                    .map(|(kl, res)| (kl.clone(), res.clone()))
+                    .unzip::<_, _, Vec<_>, Vec<_>>();
+
+                (subset.0.as_slice(), subset.1.as_slice())
+            }
+        };
+
+        let input = generate(summary, resolved, &paths);
+
+        for output in &outputs {
+            let rendered = match output.format {
+                Format::Handlebars => {
+                    let (registry, template) = output
+                        .registry
+                        .as_ref()
+                        .context("templates are always loaded for the handlebars format")?;
+                    registry.render(template, &input)?
+                }
+                Format::Json => json::render(summary, resolved, &paths, &input)?,
+                Format::SpdxJson => spdx::render_json(&krates, &paths, summary, resolved)?,
+                Format::SpdxTag => spdx::render_tag_value(&krates, &paths, summary, resolved)?,
+                Format::CyclonedxJson => {
+                    cyclonedx::render_json(&krates, &paths, summary, resolved)?
+                }
+                Format::CyclonedxXml => cyclonedx::render_xml(&krates, &paths, summary, resolved)?,
+            };
+
+            match &output.path {
+                None => println!("{}", rendered),
+                Some(path) => {
+                    let path = match &report.name {
+                        Some(name) => report_path(path, name)?,
+                        None => path.clone(),
+                    };
+
+                    std::fs::write(&path, rendered)
+                        .with_context(|| format!("output file {} could not be written", path))?;
+                }
+            }
+        }
     }
 
-    log::warn!("no 'about.toml' found, falling back to default configuration");
-    Ok(cargo_about::licenses::config::Config::default())
+    Ok(())
 }
 
-pub fn cmd(args: Args, color: crate::Color) -> anyhow::Result<()> {
-    let manifest_path = match args.manifest_path.clone() {
-        Some(mp) => mp,
-        None => {
-            let cwd =
-                std::env::current_dir().context("unable to determine current working directory")?;
-            let mut cwd = PathBuf::from_path_buf(cwd).map_err(|pb| {
-                anyhow::anyhow!(
-                    "current working directory '{}' is not a utf-8 path",
-                    pb.display()
-                )
-            })?;
-
-            cwd.push("Cargo.toml");
-            cwd
+/// An output that the gathered license information is rendered to
+struct Output {
+    format: Format,
+    /// The loaded templates and the name of the template to render, only
+    /// used by the handlebars format
+    registry: Option<(Handlebars<'static>, String)>,
+    /// The path the output is written to, stdout if not specified
+    path: Option<PathBuf>,
+}
+
+/// The name of the format, as it is specified on the command line
+fn format_name(format: Format) -> &'static str {
+    use clap::ArgEnum as _;
+
+    format
+        .to_possible_value()
+        .map_or("unknown", |pv| pv.get_name())
+}
+
+/// Determines the outputs to render, either the single output specified on
+/// the command line, or the outputs configured with `[[output]]` if a
+/// template or format isn't specified on the command line
+fn outputs(
+    args: &Args,
+    cfg: &licenses::config::Config,
+    manifest_path: &Path,
+) -> anyhow::Result<Vec<Output>> {
+    if args.templates.is_none() && args.format.is_none() && !cfg.outputs.is_empty() {
+        anyhow::ensure!(
+            args.output_file.is_none() && args.name.is_none(),
+            "`--output-file` and `--name` can only be used with a template or format specified on the command line, not with the configured outputs"
+        );
+
+        // Paths in the config are relative to the config itself
+        let root = args
+            .krates
+            .config_path(manifest_path)
+            .and_then(|config_path| config_path.parent().map(Path::to_owned))
+            .unwrap_or_default();
+
+        return cfg
+            .outputs
+            .iter()
+            .map(|output| {
+                let format = Format::from(output.format);
+                let registry = match (format, &output.template) {
+                    (Format::Handlebars, Some(template)) => {
+                        let template = if template.as_str().starts_with(BUILTIN_PREFIX) {
+                            template.clone()
+                        } else {
+                            root.join(template)
+                        };
+
+                        Some(load_templates(&template, output.name.as_deref())?)
+                    }
+                    (Format::Handlebars, None) => bail!(
+                        "the output for {} uses the handlebars format, but doesn't specify a template",
+                        output.path
+                    ),
+                    (_, template) => {
+                        anyhow::ensure!(
+                            template.is_none() && output.name.is_none(),
+                            "the output for {} specifies a template, but the '{}' format doesn't use one",
+                            output.path,
+                            format_name(format)
+                        );
+                        None
+                    }
+                };
+
+                Ok(Output {
+                    format,
+                    registry,
+                    path: Some(root.join(&output.path)),
+                })
+            })
+            .collect();
+    }
+
+    let format = args.format.unwrap_or(Format::Handlebars);
+    let registry = match (format, &args.templates) {
+        (Format::Handlebars, Some(templates)) => {
+            Some(load_templates(templates, args.name.as_deref())?)
+        }
+        (Format::Handlebars, None) => bail!("the handlebars format requires a template"),
+        (_, templates) => {
+            anyhow::ensure!(
+                templates.is_none() && args.name.is_none(),
+                "a template was specified, but the '{}' format doesn't use one",
+                format_name(format)
+            );
+            None
         }
     };
 
-    if !manifest_path.exists() {
-        bail!("cargo manifest path '{}' does not exist", manifest_path);
-    }
+    Ok(vec![Output {
+        format,
+        registry,
+        path: args
+            .output_file
+            .clone()
+            .filter(|path| path != Path::new("-")),
+    }])
+}
+
+/// A report for the crates reachable from one or more roots in the crate graph
+struct Report {
+    /// The name of the report, `None` if it's the only report
+    name: Option<String>,
+    /// The crates the report is rooted at
+    roots: Vec<krates::Kid>,
+}
 
-    let cfg = match &args.config {
-        Some(cfg_path) => {
-            let cfg_str = std::fs::read_to_string(cfg_path)
-                .with_context(|| format!("unable to read {}", cfg_path))?;
-            toml::from_str(&cfg_str)
-                .with_context(|| format!("unable to deserialize config from {}", cfg_path))?
+/// Determines the reports to generate, either a single report for every
+/// workspace member, or one for each member or binary
+fn reports(krates: &cargo_about::Krates, per: Option<Per>) -> anyhow::Result<Vec<Report>> {
+    let members = krates.workspace_members().map(|member| &member.krate);
+
+    let reports: Vec<_> = match per {
+        None => {
+            return Ok(vec![Report {
+                name: None,
+                roots: members.map(|member| member.id.clone()).collect(),
+            }])
+        }
+        Some(Per::Member) => members
+            .map(|member| Report {
+                name: Some(member.name.clone()),
+                roots: vec![member.id.clone()],
+            })
+            .collect(),
+        // Cargo resolves dependencies, including target specific ones and
+        // those enabled by a binary's required features, for the package as a
+        // whole, so the crate graph can't tell which of them each binary
+        // actually uses. Rather than generating identical, and thus
+        // misleading, reports for every binary in a package, each package can
+        // only have a single binary.
+        Some(Per::Bin) => {
+            let mut reports = Vec::new();
+
+            for member in members {
+                let bins: Vec<_> = member
+                    .targets
+                    .iter()
+                    .filter(|target| target.kind.iter().any(|kind| kind == "bin"))
+                    .map(|target| target.name.as_str())
+                    .collect();
+
+                if bins.len() > 1 {
+                    bail!(
+                        "package '{}' has multiple binaries ({}), which share its dependencies, so their reports would be identical, use `--per member` instead",
+                        member.name,
+                        bins.join(", ")
+                    );
+                }
+
+                reports.extend(bins.into_iter().map(|bin| Report {
+                    name: Some(bin.to_owned()),
+                    roots: vec![member.id.clone()],
+                }));
+            }
+
+            reports
         }
-        None => load_config(&manifest_path)?,
     };
 
-    let (all_crates, store) = rayon::join(
-        || {
-            log::info!("gathering crates for {}", manifest_path);
-            cargo_about::get_all_crates(
-                &manifest_path,
-                args.no_default_features,
-                args.all_features,
-                args.features.clone(),
-                args.workspace,
-                &cfg,
-            )
-        },
-        || {
-            log::info!("loading license store");
-            cargo_about::licenses::store_from_cache()
-        },
+    anyhow::ensure!(
+        !reports.is_empty(),
+        "unable to find any {} to generate reports for",
+        match per {
+            Some(Per::Bin) => "binaries",
+            _ => "workspace members",
+        }
     );
 
-    let krates = all_crates?;
-    let store = store?;
+    // Output file names are derived from the report names, so they must be
+    // unique, eg. a library and binary can't share the same name
+    for (i, report) in reports.iter().enumerate() {
+        if reports[..i].iter().any(|prev| prev.name == report.name) {
+            bail!(
+                "multiple reports would be named '{}'",
+                report.name.as_deref().unwrap_or_default()
+            );
+        }
+    }
 
-    log::info!("gathered {} crates", krates.len());
+    Ok(reports)
+}
+
+/// Gets the path of a named report, which is the output path with the name of
+/// the report appended to its file stem
+fn report_path(output: &Path, name: &str) -> anyhow::Result<PathBuf> {
+    let stem = output
+        .file_stem()
+        .with_context(|| format!("output file {} does not have a file name", output))?;
+
+    Ok(output.with_file_name(match output.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, name, ext),
+        None => format!("{}-{}", stem, name),
+    }))
+}
 
-    let (registry, template) = {
-        let mut reg = Handlebars::new();
+/// Emits the diagnostics for every crate, failing if any of them are errors
+fn emit_diagnostics(
+    resolved: &[Option<licenses::Resolved>],
+    files: &licenses::resolution::Files,
+    stream: term::termcolor::StandardStream,
+) -> anyhow::Result<()> {
+    use cargo_about::licenses::resolution::Severity;
+
+    let diag_cfg = term::Config::default();
+    let mut num_errors = 0;
 
-        if !args.templates.exists() {
-            bail!("template(s) path {} does not exist", args.templates);
+    let mut streaml = stream.lock();
+    for diag in resolved.iter().flatten().flat_map(|res| &res.diagnostics) {
+        if diag.severity >= Severity::Error {
+            num_errors += 1;
         }
 
-        use handlebars::*;
-
-        reg.register_helper(
-            "json",
-            Box::new(
-                |h: &Helper<'_, '_>,
-                 _r: &Handlebars<'_>,
-                 _: &Context,
-                 _rc: &mut RenderContext<'_, '_>,
-                 out: &mut dyn Output|
-                 -> HelperResult {
-                    let param = h
-                        .param(0)
-                        .ok_or_else(|| RenderError::new("param not found"))?;
-
-                    out.write(&serde_json::to_string_pretty(param.value())?)?;
-                    Ok(())
-                },
-            ),
+        term::emit(&mut streaml, &diag_cfg, files, diag)?;
+    }
+
+    if num_errors > 0 {
+        anyhow::bail!(
+            "encountered {} errors resolving licenses, unable to generate output",
+            num_errors
         );
+    }
 
-        if args.templates.is_dir() {
-            reg.register_templates_directory(".hbs", &args.templates)?;
+    Ok(())
+}
 
-            if reg.get_templates().is_empty() {
-                bail!(
-                    "template path {} did not contain any hbs files",
-                    args.templates
-                );
-            }
+/// The current time as an ISO-8601 UTC timestamp. Respects `SOURCE_DATE_EPOCH`
+/// so that documents can be reproducibly generated
+fn timestamp() -> String {
+    let now = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|sde| sde.parse::<i64>().ok())
+        .and_then(|sde| time::OffsetDateTime::from_unix_timestamp(sde).ok())
+        .unwrap_or_else(time::OffsetDateTime::now_utc);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        now.year(),
+        now.month() as u8,
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    )
+}
 
-            (reg, args.name.context("specified a directory for templates, but did not provide the name of the template to use")?)
-        } else {
-            // Ignore the extension, if the user says they want to use a specific file, that's on them
-            reg.register_template_file("tmpl", args.templates)?;
+/// The license expression declared in the crate's manifest, if it is present
+/// and a valid SPDX expression
+fn declared_license(krate: &cargo_about::Krate) -> Option<::spdx::Expression> {
+    krate
+        .license
+        .as_deref()
+        .and_then(|lic| ::spdx::Expression::parse(lic).ok())
+}
 
-            (reg, "tmpl".to_owned())
+/// The prefix used to select a built-in template rather than a path
+const BUILTIN_PREFIX: &str = "builtin:";
+
+/// The templates that are built into the binary, selected with
+/// `builtin:<name>`
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("html", include_str!("../../resources/templates/html.hbs")),
+    (
+        "markdown",
+        include_str!("../../resources/templates/markdown.hbs"),
+    ),
+    ("text", include_str!("../../resources/templates/text.hbs")),
+    (
+        "notice",
+        include_str!("../../resources/templates/notice.hbs"),
+    ),
+];
+
+fn load_templates<'h>(
+    templates: &Path,
+    name: Option<&str>,
+) -> anyhow::Result<(Handlebars<'h>, String)> {
+    let mut reg = Handlebars::new();
+
+    helpers::register(&mut reg);
+
+    let template = if let Some(builtin) = templates.as_str().strip_prefix(BUILTIN_PREFIX) {
+        if !BUILTIN_TEMPLATES.iter().any(|(name, _)| *name == builtin) {
+            bail!(
+                "unknown built-in template '{}', expected one of: {}",
+                builtin,
+                BUILTIN_TEMPLATES
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
         }
-    };
 
-    let client = cd::client::Client::new();
-    let summary = licenses::Gatherer::with_store(std::sync::Arc::new(store), client)
-        .with_confidence_threshold(args.threshold)
-        .gather(&krates, &cfg);
+        builtin.to_owned()
+    } else if !templates.exists() {
+        bail!("template(s) path {} does not exist", templates);
+    } else if templates.is_dir() {
+        reg.register_templates_directory(".hbs", templates)?;
+        helpers::register_scripts(&mut reg, templates)?;
 
-    let (files, resolved) = licenses::resolution::resolve(&summary, &cfg.accepted, &cfg.crates);
+        if reg.get_templates().is_empty() {
+            bail!("template path {} did not contain any hbs files", templates);
+        }
 
-    use term::termcolor::ColorChoice;
+        let name = name.context("specified a directory for templates, but did not provide the name of the template to use")?;
+        name.to_owned()
+    } else {
+        // Ignore the extension, if the user says they want to use a specific file, that's on them
+        reg.register_template_file("tmpl", templates)?;
+        "tmpl".to_owned()
+    };
 
-    let stream = term::termcolor::StandardStream::stderr(match color {
-        crate::Color::Auto => {
-            // The termcolor crate doesn't check the stream to see if it's a TTY
-            // which doesn't really fit with how the rest of the coloring works
-            if atty::is(atty::Stream::Stderr) {
-                ColorChoice::Auto
-            } else {
-                ColorChoice::Never
-            }
-        }
-        crate::Color::Always => ColorChoice::Always,
-        crate::Color::Never => ColorChoice::Never,
-    });
-
-    let output = generate(&summary, &resolved, &files, stream, &registry, &template)?;
-
-    match args.output_file.as_ref() {
-        None => println!("{}", output),
-        Some(path) if path == Path::new("-") => println!("{}", output),
-        Some(path) => {
-            std::fs::write(path, output)
-                .with_context(|| format!("output file {} could not be written", path))?;
+    // The built-in templates are always available, eg. as partials, but are
+    // overridden by user templates with the same name
+    for (name, template) in BUILTIN_TEMPLATES {
+        if !reg.has_template(name) {
+            reg.register_template_string(name, template)?;
         }
     }
 
-    Ok(())
+    Ok((reg, template))
 }
 
 #[derive(Clone, Serialize)]
 struct UsedBy<'a> {
     #[serde(rename = "crate")]
     krate: &'a krates::cm::Package,
+    /// The shortest dependency path from each workspace member that depends
+    /// on the crate, starting with the workspace member
+    paths: Vec<Vec<&'a krates::cm::Package>>,
+    /// Deprecated, this was never set, use `paths` instead. Kept so existing
+    /// templates that check it continue to render.
     path: Option<PathBuf>,
 }
 
+impl<'a> UsedBy<'a> {
+    fn new(krate: &'a cargo_about::Krate, paths: &cargo_about::DependencyPaths<'a>) -> Self {
+        Self {
+            krate,
+            paths: paths
+                .paths(&krate.id)
+                .into_iter()
+                .map(|path| path.into_iter().map(|krate| &krate.0).collect())
+                .collect(),
+            path: None,
+        }
+    }
+}
+
 #[derive(Clone, Serialize)]
 struct License<'a> {
     /// The full name of the license
@@ -238,10 +562,22 @@ struct License<'a> {
     text: String,
     /// The path where the license text was sourced from
     source_path: Option<PathBuf>,
+    /// The copyright statements in the license text
+    copyrights: Vec<licenses::copyright::Copyright>,
     /// The list of crates this license was applied to
     used_by: Vec<UsedBy<'a>>,
 }
 
+#[derive(Clone, Serialize)]
+struct Notice<'a> {
+    /// The path of the NOTICE file the text was sourced from
+    path: PathBuf,
+    /// The full text of the NOTICE file
+    text: String,
+    /// The list of crates that have this NOTICE
+    used_by: Vec<UsedBy<'a>>,
+}
+
 #[derive(Serialize)]
 struct LicenseSet {
     count: usize,
@@ -255,23 +591,15 @@ struct LicenseSet {
 struct Input<'a> {
     overview: Vec<LicenseSet>,
     licenses: Vec<License<'a>>,
+    notices: Vec<Notice<'a>>,
     crates: Vec<PackageLicense<'a>>,
 }
 
-fn generate(
-    nfos: &[licenses::KrateLicense<'_>],
+fn generate<'kl>(
+    nfos: &'kl [licenses::KrateLicense<'_>],
     resolved: &[Option<licenses::Resolved>],
-    files: &licenses::resolution::Files,
-    stream: term::termcolor::StandardStream,
-    hbs: &Handlebars<'_>,
-    template_name: &str,
-) -> anyhow::Result<String> {
-    use cargo_about::licenses::resolution::Severity;
-
-    let mut num_errors = 0;
-
-    let diag_cfg = term::Config::default();
-
+    paths: &cargo_about::DependencyPaths<'kl>,
+) -> Input<'kl> {
     let licenses = {
         let mut licenses = BTreeMap::new();
         for (krate_license, resolved) in nfos
@@ -279,22 +607,10 @@ fn generate(
             .zip(resolved.iter())
             .filter_map(|(kl, res)| res.as_ref().map(|res| (kl, res)))
         {
-            if !resolved.diagnostics.is_empty() {
-                let mut streaml = stream.lock();
-
-                for diag in &resolved.diagnostics {
-                    if diag.severity >= Severity::Error {
-                        num_errors += 1;
-                    }
-
-                    term::emit(&mut streaml, &diag_cfg, files, diag)?;
-                }
-            }
-
             let license_iter = resolved.licenses.iter().flat_map(|license| {
                 let mut license_texts = Vec::new();
                 match license.license {
-                    spdx::LicenseItem::Spdx { id, .. } => {
+                    ::spdx::LicenseItem::Spdx { id, .. } => {
                         // Attempt to retrieve the actual license file from the crate, note that in some cases
                         // _sigh_ there are actually multiple license texts for the same license with different
                         // copyright holders/authors/attribution so we can't just return 1
@@ -318,6 +634,8 @@ fn generate(
                                             id: id.name.to_owned(),
                                             text: text.clone(),
                                             source_path: Some(lf.path.clone()),
+                                            copyrights: krate_license
+                                                .license_file_copyrights(lf),
                                             used_by: Vec::new(),
                                         };
                                         Some(license)
@@ -340,11 +658,12 @@ fn generate(
                                 id: id.name.to_owned(),
                                 text: id.text().to_owned(),
                                 source_path: None,
+                                copyrights: Vec::new(),
                                 used_by: Vec::new(),
                             });
                         }
                     }
-                    spdx::LicenseItem::Other { .. } => {
+                    ::spdx::LicenseItem::Other { .. } => {
                         log::warn!(
                             "{} has no license file for crate '{}'",
                             license,
@@ -362,16 +681,13 @@ fn generate(
                     .or_insert_with(BTreeMap::new);
 
                 let lic = entry.entry(license.text.clone()).or_insert_with(|| license);
-                lic.used_by.push(UsedBy {
-                    krate: krate_license.krate,
-                    path: None,
-                });
+                lic.used_by.push(UsedBy::new(krate_license.krate, paths));
             }
         }
 
         let mut licenses: Vec<_> = licenses
-            .into_iter()
-            .flat_map(|(_, v)| v.into_iter().map(|(_, v)| v))
+            .into_values()
+            .flat_map(|v| v.into_values())
             .collect();
 
         // Sort the krates that use a license lexicographically
@@ -383,13 +699,6 @@ fn generate(
         licenses
     };
 
-    if num_errors > 0 {
-        anyhow::bail!(
-            "encountered {} errors resolving licenses, unable to generate output",
-            num_errors
-        );
-    }
-
     let mut overview: Vec<LicenseSet> = Vec::with_capacity(256);
 
     for (ndx, lic) in licenses.iter().enumerate() {
@@ -410,9 +719,43 @@ fn generate(
         }
     }
 
-    overview.iter_mut().for_each(|i| i.count = i.indices.len());
+    for i in &mut overview {
+        i.count = i.indices.len();
+    }
     // Show the most used licenses first
-    overview.sort_by(|a, b| b.count.cmp(&a.count));
+    overview.sort_by_key(|i| std::cmp::Reverse(i.count));
+
+    let notices = {
+        // Crates in the same repository frequently share the same NOTICE, so
+        // they are deduplicated by their text
+        let mut notices = BTreeMap::new();
+        for (nfo, notice) in nfos
+            .iter()
+            .zip(resolved.iter())
+            .filter(|(_, res)| res.is_some())
+            .flat_map(|(nfo, _)| nfo.notices.iter().map(move |notice| (nfo, notice)))
+        {
+            let entry = notices
+                .entry(notice.text.as_str())
+                .or_insert_with(|| Notice {
+                    path: notice.path.clone(),
+                    text: notice.text.clone(),
+                    used_by: Vec::new(),
+                });
+
+            entry.used_by.push(UsedBy::new(nfo.krate, paths));
+        }
+
+        let mut notices: Vec<_> = notices.into_values().collect();
+
+        for notice in &mut notices {
+            notice.used_by.sort_by(|a, b| a.krate.id.cmp(&b.krate.id));
+        }
+
+        // Keep the output stable by ordering on the first crate to use each one
+        notices.sort_by(|a, b| a.used_by[0].krate.id.cmp(&b.used_by[0].krate.id));
+        notices
+    };
 
     let crates = nfos
         .iter()
@@ -420,19 +763,260 @@ fn generate(
         .map(|nfo| PackageLicense {
             package: &nfo.krate.0,
             license: nfo.lic_info.to_string(),
+            provenance: &nfo.provenance,
+            copyrights: &nfo.copyrights,
         })
         .collect();
-    let nput = Input {
+    Input {
         overview,
         licenses,
+        notices,
         crates,
-    };
-
-    Ok(hbs.render(template_name, &nput)?)
+    }
 }
 
 #[derive(Serialize)]
 struct PackageLicense<'a> {
     package: &'a Package,
     license: String,
+    provenance: &'a licenses::Provenance,
+    copyrights: &'a [licenses::copyright::Copyright],
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// A workspace with a path crate that depends on a registry crate, in a
+    /// temporary directory that is removed when dropped
+    pub(crate) struct Workspace {
+        pub(crate) root: PathBuf,
+        pub(crate) krates: cargo_about::Krates,
+    }
+
+    impl Workspace {
+        pub(crate) fn new(name: &str) -> Self {
+            let root = PathBuf::from_path_buf(std::env::temp_dir())
+                .unwrap()
+                .join(format!("cargo-about-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+
+            let write = |path: &str, contents: &str| {
+                let path = root.join(path);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, contents).unwrap();
+            };
+
+            write("app/Cargo.toml", "[package]\nname = \"app\"\n");
+            write("app/LICENSE", "app license");
+            write("dep/Cargo.toml", "[package]\nname = \"dep\"\n");
+            write("dep/LICENSE", "dep license");
+            write("dep/src/lib.rs", "");
+            write("dep/.cargo-ok", "ok");
+
+            let app_id = format!("app 0.1.0 (path+file://{}/app)", root);
+            let dep_id =
+                "dep 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)".to_owned();
+            let package = |name: &str, id: &str, source: Option<&str>, deps| {
+                serde_json::json!({
+                    "name": name,
+                    "version": if name == "app" { "0.1.0" } else { "1.0.0" },
+                    "id": id,
+                    "source": source,
+                    "license": "MIT",
+                    "dependencies": deps,
+                    "targets": [],
+                    "features": {},
+                    "manifest_path": root.join(name).join("Cargo.toml"),
+                })
+            };
+
+            let md = serde_json::json!({
+                "packages": [
+                    package("app", &app_id, None, serde_json::json!([{
+                        "name": "dep",
+                        "source": "registry+https://github.com/rust-lang/crates.io-index",
+                        "req": "^1",
+                        "kind": null,
+                        "optional": false,
+                        "uses_default_features": true,
+                        "features": [],
+                        "target": null,
+                        "rename": null,
+                        "registry": null,
+                    }])),
+                    package(
+                        "dep",
+                        &dep_id,
+                        Some("registry+https://github.com/rust-lang/crates.io-index"),
+                        serde_json::json!([]),
+                    ),
+                ],
+                "workspace_members": [app_id],
+                "resolve": {
+                    "nodes": [
+                        {
+                            "id": app_id,
+                            "dependencies": [dep_id],
+                            "deps": [{
+                                "name": "dep",
+                                "pkg": dep_id,
+                                "dep_kinds": [{ "kind": null, "target": null }],
+                            }],
+                            "features": [],
+                        },
+                        { "id": dep_id, "dependencies": [], "deps": [], "features": [] },
+                    ],
+                    "root": app_id,
+                },
+                "workspace_root": root,
+                "target_directory": root.join("target"),
+                "version": 1,
+            });
+
+            let krates = krates::Builder::new()
+                .build_with_metadata(serde_json::from_value(md).unwrap(), |_| {})
+                .unwrap();
+
+            Self { root, krates }
+        }
+    }
+
+    impl Drop for Workspace {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    pub(crate) fn license_file(path: PathBuf, text: &str) -> licenses::LicenseFile {
+        licenses::LicenseFile {
+            license_expr: ::spdx::Expression::parse("MIT").unwrap(),
+            path,
+            confidence: 1.0,
+            kind: licenses::LicenseFileKind::Text(text.to_owned()),
+            range: None,
+            provenance: licenses::Provenance::FileSystem,
+        }
+    }
+
+    use clap::Parser as _;
+
+    #[test]
+    fn rejects_templates_for_other_formats() {
+        let manifest_path = Path::new("Cargo.toml");
+        let outputs = |args: &[&str], cfg: &str| {
+            let args = Args::parse_from(std::iter::once("generate").chain(args.iter().copied()));
+            let cfg: licenses::config::Config =
+                toml::from_str(&format!("accepted = []\n{}", cfg)).unwrap();
+            outputs(&args, &cfg, manifest_path).map(|outputs| outputs.len())
+        };
+
+        assert_eq!(outputs(&["--format", "json"], "").unwrap(), 1);
+        assert!(outputs(&["--format", "json", "about.hbs"], "").is_err());
+        assert!(outputs(&["--format", "spdx-json", "--name", "about"], "").is_err());
+
+        assert_eq!(
+            outputs(
+                &[],
+                "[[output]]\nformat = \"json\"\npath = \"about.json\"\n"
+            )
+            .unwrap(),
+            1
+        );
+        assert!(outputs(
+            &[],
+            "[[output]]\nformat = \"json\"\ntemplate = \"about.hbs\"\npath = \"about.json\"\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn renders_builtin_templates() {
+        let ws = Workspace::new("builtin");
+        let krate = |name: &str| {
+            &ws.krates
+                .krates()
+                .find(|kn| kn.krate.name == name)
+                .unwrap()
+                .krate
+        };
+
+        let dep_license = "Copyright (c) 2021 Dep <Authors>\n\ndep license & more";
+        let nfos = vec![
+            licenses::KrateLicense {
+                krate: krate("app"),
+                lic_info: licenses::LicenseInfo::Expr(::spdx::Expression::parse("MIT").unwrap()),
+                provenance: licenses::Provenance::FileSystem,
+                license_files: vec![license_file(ws.root.join("app/LICENSE"), "app license")],
+                notices: Vec::new(),
+                copyrights: Vec::new(),
+            },
+            licenses::KrateLicense {
+                krate: krate("dep"),
+                lic_info: licenses::LicenseInfo::Expr(::spdx::Expression::parse("MIT").unwrap()),
+                provenance: licenses::Provenance::FileSystem,
+                license_files: vec![license_file(ws.root.join("dep/LICENSE"), dep_license)],
+                notices: vec![licenses::Notice {
+                    path: "NOTICE".into(),
+                    text: "dep notice".to_owned(),
+                }],
+                copyrights: licenses::copyright::extract(dep_license),
+            },
+        ];
+
+        let cfg: licenses::config::Config = toml::from_str(r#"accepted = ["MIT"]"#).unwrap();
+        let (_, resolved) = licenses::resolution::resolve(&nfos, &cfg);
+        let paths = cargo_about::DependencyPaths::new(&ws.krates);
+        let input = generate(&nfos, &resolved, &paths);
+
+        for (name, _) in BUILTIN_TEMPLATES {
+            let path = PathBuf::from(format!("{}{}", BUILTIN_PREFIX, name));
+            let (reg, template) = load_templates(&path, None).unwrap();
+
+            assert_eq!(template, *name);
+            let output = reg.render(&template, &input).unwrap();
+
+            let expected: &[&str] = match *name {
+                "html" => &[
+                    r##"<li><a href="#MIT">MIT License</a> (2)</li>"##,
+                    r#"<a href=" https://crates.io/crates/dep ">dep 1.0.0</a>"#,
+                    "Copyright (c) 2021 Dep &lt;Authors&gt;\n\ndep license &amp; more</pre>",
+                    r#"<pre class="license-text">app license</pre>"#,
+                    r#"<pre class="license-text">dep notice</pre>"#,
+                ],
+                "markdown" => &[
+                    "- [MIT License](#MIT) (2)",
+                    "- [dep 1.0.0](https://crates.io/crates/dep)",
+                    "```text\nCopyright (c) 2021 Dep <Authors>\n\ndep license & more\n```",
+                    "## Notices",
+                    "```text\ndep notice\n```",
+                ],
+                "text" => &[
+                    "MIT License\n\nUsed by:\n    dep 1.0.0\n",
+                    "Copyright (c) 2021 Dep <Authors>\n\ndep license & more",
+                    "Used by:\n    app 0.1.0\n",
+                    "NOTICE\n\nUsed by:\n    dep 1.0.0\n",
+                    "dep notice",
+                ],
+                "notice" => &[
+                    "app 0.1.0\n    License: MIT\n",
+                    "dep 1.0.0\n    License: MIT\n    Copyright (c) 2021 Dep <Authors>\n",
+                    "NOTICE for dep 1.0.0\n\ndep notice\n",
+                ],
+                _ => unreachable!("builtin template '{}' is not tested", name),
+            };
+
+            for fragment in expected {
+                assert!(
+                    output.contains(fragment),
+                    "'{}' is missing from '{}':\n{}",
+                    fragment,
+                    name,
+                    output
+                );
+            }
+        }
+
+        assert!(load_templates(Path::new("builtin:pdf"), None).is_err());
+    }
 }
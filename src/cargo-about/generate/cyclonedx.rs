@@ -0,0 +1,655 @@
+//! Exports the gathered license information as a
+//! [CycloneDX 1.5](https://cyclonedx.org/docs/1.5/json/) bill of materials, in
+//! either the JSON or XML serialization
+
+use crate::common::xml_escape as escape;
+use cargo_about::{
+    licenses::{self, LicenseInfo},
+    Krate, Krates,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write as _,
+};
+
+const SPEC_VERSION: &str = "1.5";
+
+#[derive(Serialize)]
+struct Property {
+    name: &'static str,
+    value: String,
+}
+
+/// A single license, `CycloneDX` distinguishes between licenses on the SPDX
+/// license list and those that aren't
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum LicenseChoice {
+    Id(String),
+    Name(String),
+}
+
+#[derive(Serialize)]
+struct License {
+    #[serde(flatten)]
+    choice: LicenseChoice,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    properties: Vec<Property>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Licenses {
+    Expression(String),
+    License(License),
+}
+
+#[derive(Serialize)]
+struct Occurrence {
+    location: String,
+}
+
+#[derive(Serialize)]
+struct Evidence {
+    occurrences: Vec<Occurrence>,
+    licenses: Vec<Licenses>,
+}
+
+#[derive(Serialize)]
+struct Component {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(rename = "bom-ref")]
+    bom_ref: String,
+    name: String,
+    version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// The copyright statements in the crate's license files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    copyright: Option<String>,
+    /// The license expression declared by the crate's authors
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    licenses: Vec<Licenses>,
+    purl: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    properties: Vec<Property>,
+    /// The license files the crate's licenses were detected in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    evidence: Option<Evidence>,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Serialize)]
+struct Tools {
+    components: Vec<Tool>,
+}
+
+#[derive(Serialize)]
+struct Metadata {
+    timestamp: String,
+    tools: Tools,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    component: Option<Component>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Dependency {
+    #[serde(rename = "ref")]
+    bom_ref: String,
+    depends_on: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Bom {
+    bom_format: &'static str,
+    spec_version: &'static str,
+    serial_number: String,
+    version: u32,
+    metadata: Metadata,
+    components: Vec<Component>,
+    dependencies: Vec<Dependency>,
+}
+
+fn license(item: &spdx::LicenseItem, properties: Vec<Property>) -> License {
+    let choice = match item {
+        spdx::LicenseItem::Spdx { id, .. } => LicenseChoice::Id(id.name.to_owned()),
+        other @ spdx::LicenseItem::Other { .. } => LicenseChoice::Name(other.to_string()),
+    };
+
+    License { choice, properties }
+}
+
+fn component(
+    nfo: &licenses::KrateLicense<'_>,
+    resolved: Option<&licenses::Resolved>,
+    kind: &'static str,
+) -> Component {
+    let krate: &Krate = nfo.krate;
+    let root = krate.manifest_path.parent();
+
+    let mut licenses = Vec::new();
+    let mut occurrences = Vec::new();
+
+    for lf in &nfo.license_files {
+        let path = root
+            .and_then(|root| lf.path.strip_prefix(root).ok())
+            .unwrap_or(&lf.path);
+
+        for er in lf.license_expr.requirements() {
+            licenses.push(Licenses::License(license(
+                &er.req.license,
+                vec![
+                    Property {
+                        name: "cargo-about:path",
+                        value: path.to_string(),
+                    },
+                    Property {
+                        name: "cargo-about:confidence",
+                        value: lf.confidence.to_string(),
+                    },
+                    Property {
+                        name: "cargo-about:provenance",
+                        value: lf.provenance.to_string(),
+                    },
+                ],
+            )));
+        }
+
+        occurrences.push(Occurrence {
+            location: path.to_string(),
+        });
+    }
+
+    let properties = std::iter::once(Property {
+        name: "cargo-about:provenance",
+        value: nfo.provenance.to_string(),
+    })
+    .chain(
+        resolved
+            .into_iter()
+            .flat_map(|res| res.licenses.iter())
+            .map(|req| Property {
+                name: "cargo-about:concluded",
+                value: req.to_string(),
+            }),
+    )
+    .chain(nfo.notices.iter().map(|notice| Property {
+        name: "cargo-about:notice",
+        value: notice.text.clone(),
+    }))
+    .collect();
+
+    Component {
+        kind,
+        bom_ref: krate.id.repr.clone(),
+        name: krate.name.clone(),
+        version: krate.version.to_string(),
+        description: krate.description.clone(),
+        copyright: (!nfo.copyrights.is_empty()).then(|| {
+            nfo.copyrights
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        }),
+        licenses: super::declared_license(krate)
+            .map(|expr| Licenses::Expression(expr.to_string()))
+            .into_iter()
+            .collect(),
+        purl: format!("pkg:cargo/{}@{}", krate.name, krate.version),
+        properties,
+        evidence: (!occurrences.is_empty()).then(|| Evidence {
+            occurrences,
+            licenses,
+        }),
+    }
+}
+
+impl Bom {
+    fn new(
+        krates: &Krates,
+        paths: &cargo_about::DependencyPaths<'_>,
+        nfos: &[licenses::KrateLicense<'_>],
+        resolved: &[Option<licenses::Resolved>],
+    ) -> Self {
+        let included: Vec<_> = nfos
+            .iter()
+            .zip(resolved.iter())
+            .filter(|(nfo, _)| !matches!(nfo.lic_info, LicenseInfo::Ignore))
+            .collect();
+
+        // If there is only a single root, normally a workspace member, it's
+        // the subject of the bom, otherwise the roots are just regular
+        // components
+        let root = {
+            let mut roots = paths.roots();
+            match (roots.next(), roots.next()) {
+                (Some(root), None) => Some(&root.id),
+                _ => None,
+            }
+        };
+
+        let mut component_meta = None;
+        let mut components = Vec::with_capacity(included.len());
+        for (nfo, resolved) in &included {
+            if Some(&nfo.krate.id) == root {
+                component_meta = Some(component(nfo, resolved.as_ref(), "application"));
+            } else {
+                components.push(component(nfo, resolved.as_ref(), "library"));
+            }
+        }
+
+        let refs: HashMap<_, _> = included
+            .iter()
+            .map(|(nfo, _)| (&nfo.krate.id, nfo.krate.id.repr.clone()))
+            .collect();
+
+        let dependencies = included
+            .iter()
+            .filter_map(|(nfo, _)| {
+                let nid = krates.nid_for_kid(&nfo.krate.id)?;
+
+                let depends_on: BTreeSet<_> = krates
+                    .get_deps(nid)
+                    .filter_map(|(dep, _)| refs.get(&dep.id).cloned())
+                    .collect();
+
+                Some(Dependency {
+                    bom_ref: nfo.krate.id.repr.clone(),
+                    depends_on: depends_on.into_iter().collect(),
+                })
+            })
+            .collect();
+
+        let timestamp = super::timestamp();
+
+        // The serial number must be unique for every bom, but two boms
+        // created at the same time for the same crates are the same bom
+        let serial_number = {
+            use std::hash::Hasher;
+
+            let mut hashes = [0u64; 2];
+            for (seed, hash) in hashes.iter_mut().enumerate() {
+                let mut hasher = twox_hash::XxHash64::with_seed(seed as u64);
+
+                hasher.write(timestamp.as_bytes());
+                for (nfo, _) in &included {
+                    hasher.write(nfo.krate.id.repr.as_bytes());
+                }
+
+                *hash = hasher.finish();
+            }
+
+            // Format as a version 4 uuid
+            let [hi, lo] = hashes;
+            let hi = (hi & !0xf000) | 0x4000;
+            let lo = (lo & !(0xc << 60)) | (0x8 << 60);
+
+            format!(
+                "urn:uuid:{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+                hi >> 32,
+                (hi >> 16) & 0xffff,
+                hi & 0xffff,
+                lo >> 48,
+                lo & 0xffff_ffff_ffff
+            )
+        };
+
+        Self {
+            bom_format: "CycloneDX",
+            spec_version: SPEC_VERSION,
+            serial_number,
+            version: 1,
+            metadata: Metadata {
+                timestamp,
+                tools: Tools {
+                    components: vec![Tool {
+                        kind: "application",
+                        name: env!("CARGO_PKG_NAME"),
+                        version: env!("CARGO_PKG_VERSION"),
+                    }],
+                },
+                component: component_meta,
+            },
+            components,
+            dependencies,
+        }
+    }
+
+    fn to_xml(&self) -> Result<String, std::fmt::Error> {
+        fn properties(out: &mut String, props: &[Property], indent: &str) -> std::fmt::Result {
+            if props.is_empty() {
+                return Ok(());
+            }
+
+            writeln!(out, "{}<properties>", indent)?;
+            for prop in props {
+                writeln!(
+                    out,
+                    "{}  <property name=\"{}\">{}</property>",
+                    indent,
+                    escape(prop.name),
+                    escape(&prop.value)
+                )?;
+            }
+            writeln!(out, "{}</properties>", indent)
+        }
+
+        fn licenses(out: &mut String, lics: &[Licenses], indent: &str) -> std::fmt::Result {
+            writeln!(out, "{}<licenses>", indent)?;
+            for lic in lics {
+                match lic {
+                    Licenses::Expression(expr) => {
+                        writeln!(out, "{}  <expression>{}</expression>", indent, escape(expr))?;
+                    }
+                    Licenses::License(lic) => {
+                        writeln!(out, "{}  <license>", indent)?;
+                        match &lic.choice {
+                            LicenseChoice::Id(id) => {
+                                writeln!(out, "{}    <id>{}</id>", indent, escape(id))?;
+                            }
+                            LicenseChoice::Name(name) => {
+                                writeln!(out, "{}    <name>{}</name>", indent, escape(name))?;
+                            }
+                        }
+                        properties(out, &lic.properties, &format!("{}    ", indent))?;
+                        writeln!(out, "{}  </license>", indent)?;
+                    }
+                }
+            }
+            writeln!(out, "{}</licenses>", indent)
+        }
+
+        // Note the order of elements matters, as the schema uses sequences
+        fn component(out: &mut String, comp: &Component, indent: &str) -> std::fmt::Result {
+            writeln!(
+                out,
+                "{}<component type=\"{}\" bom-ref=\"{}\">",
+                indent,
+                comp.kind,
+                escape(&comp.bom_ref)
+            )?;
+            writeln!(out, "{}  <name>{}</name>", indent, escape(&comp.name))?;
+            writeln!(
+                out,
+                "{}  <version>{}</version>",
+                indent,
+                escape(&comp.version)
+            )?;
+            if let Some(desc) = &comp.description {
+                writeln!(
+                    out,
+                    "{}  <description>{}</description>",
+                    indent,
+                    escape(desc)
+                )?;
+            }
+
+            let inner = format!("{}  ", indent);
+            if !comp.licenses.is_empty() {
+                licenses(out, &comp.licenses, &inner)?;
+            }
+            if let Some(copyright) = &comp.copyright {
+                writeln!(
+                    out,
+                    "{}  <copyright>{}</copyright>",
+                    indent,
+                    escape(copyright)
+                )?;
+            }
+            writeln!(out, "{}  <purl>{}</purl>", indent, escape(&comp.purl))?;
+            properties(out, &comp.properties, &inner)?;
+
+            if let Some(evidence) = &comp.evidence {
+                writeln!(out, "{}  <evidence>", indent)?;
+                writeln!(out, "{}    <occurrences>", indent)?;
+                for occ in &evidence.occurrences {
+                    writeln!(
+                        out,
+                        "{}      <occurrence><location>{}</location></occurrence>",
+                        indent,
+                        escape(&occ.location)
+                    )?;
+                }
+                writeln!(out, "{}    </occurrences>", indent)?;
+                licenses(out, &evidence.licenses, &format!("{}    ", indent))?;
+                writeln!(out, "{}  </evidence>", indent)?;
+            }
+
+            writeln!(out, "{}</component>", indent)
+        }
+
+        let mut out = String::new();
+
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<bom xmlns="http://cyclonedx.org/schema/bom/{}" serialNumber="{}" version="{}">"#,
+            self.spec_version, self.serial_number, self.version
+        )?;
+
+        writeln!(out, "  <metadata>")?;
+        writeln!(
+            out,
+            "    <timestamp>{}</timestamp>",
+            self.metadata.timestamp
+        )?;
+        writeln!(out, "    <tools>")?;
+        writeln!(out, "      <components>")?;
+        for tool in &self.metadata.tools.components {
+            writeln!(out, "        <component type=\"{}\">", tool.kind)?;
+            writeln!(out, "          <name>{}</name>", tool.name)?;
+            writeln!(out, "          <version>{}</version>", tool.version)?;
+            writeln!(out, "        </component>")?;
+        }
+        writeln!(out, "      </components>")?;
+        writeln!(out, "    </tools>")?;
+        if let Some(comp) = &self.metadata.component {
+            component(&mut out, comp, "    ")?;
+        }
+        writeln!(out, "  </metadata>")?;
+
+        writeln!(out, "  <components>")?;
+        for comp in &self.components {
+            component(&mut out, comp, "    ")?;
+        }
+        writeln!(out, "  </components>")?;
+
+        writeln!(out, "  <dependencies>")?;
+        for dep in &self.dependencies {
+            if dep.depends_on.is_empty() {
+                writeln!(out, "    <dependency ref=\"{}\"/>", escape(&dep.bom_ref))?;
+                continue;
+            }
+
+            writeln!(out, "    <dependency ref=\"{}\">", escape(&dep.bom_ref))?;
+            for on in &dep.depends_on {
+                writeln!(out, "      <dependency ref=\"{}\"/>", escape(on))?;
+            }
+            writeln!(out, "    </dependency>")?;
+        }
+        writeln!(out, "  </dependencies>")?;
+
+        writeln!(out, "</bom>")?;
+
+        Ok(out)
+    }
+}
+
+/// Renders a `CycloneDX` bom in the JSON serialization
+pub(super) fn render_json(
+    krates: &Krates,
+    paths: &cargo_about::DependencyPaths<'_>,
+    nfos: &[licenses::KrateLicense<'_>],
+    resolved: &[Option<licenses::Resolved>],
+) -> anyhow::Result<String> {
+    let bom = Bom::new(krates, paths, nfos, resolved);
+    Ok(serde_json::to_string_pretty(&bom)?)
+}
+
+/// Renders a `CycloneDX` bom in the XML serialization
+pub(super) fn render_xml(
+    krates: &Krates,
+    paths: &cargo_about::DependencyPaths<'_>,
+    nfos: &[licenses::KrateLicense<'_>],
+    resolved: &[Option<licenses::Resolved>],
+) -> anyhow::Result<String> {
+    let bom = Bom::new(krates, paths, nfos, resolved);
+    Ok(bom.to_xml()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generate::test::{license_file, Workspace};
+
+    /// Gets the names of the child elements of the first element that starts
+    /// with `start`, which must be on its own line, in document order
+    fn children<'x>(xml: &'x str, start: &str) -> Vec<&'x str> {
+        let begin = xml.find(start).unwrap();
+        let indent = xml[..begin].len() - xml[..begin].trim_end_matches(' ').len();
+        let child_indent = " ".repeat(indent + 2);
+        let end_indent = " ".repeat(indent);
+
+        xml[begin..]
+            .lines()
+            .skip(1)
+            .take_while(|line| !(line.starts_with(&end_indent) && line[indent..].starts_with("</")))
+            .filter_map(|line| line.strip_prefix(&child_indent))
+            .filter_map(|line| line.strip_prefix('<'))
+            .filter(|line| !line.starts_with('/'))
+            .map(|line| line.split(|c: char| c == '>' || c == ' ').next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn generates_valid_document() {
+        let ws = Workspace::new("cyclonedx");
+        let app = ws
+            .krates
+            .krates()
+            .find(|kn| kn.krate.name == "app")
+            .unwrap();
+        let dep = ws
+            .krates
+            .krates()
+            .find(|kn| kn.krate.name == "dep")
+            .unwrap();
+
+        let krate_license = |krate, license_files, notices| licenses::KrateLicense {
+            krate,
+            lic_info: LicenseInfo::Expr(spdx::Expression::parse("MIT").unwrap()),
+            provenance: licenses::Provenance::FileSystem,
+            license_files,
+            notices,
+            copyrights: Vec::new(),
+        };
+
+        let nfos = vec![
+            krate_license(
+                &app.krate,
+                vec![license_file(ws.root.join("app/LICENSE"), "app license")],
+                Vec::new(),
+            ),
+            krate_license(
+                &dep.krate,
+                vec![license_file(ws.root.join("dep/LICENSE"), "dep license")],
+                vec![licenses::Notice {
+                    path: "NOTICE".into(),
+                    text: "Copyright <Dep> & \"Friends\"".to_owned(),
+                }],
+            ),
+        ];
+        let resolved = vec![None, None];
+        let paths = cargo_about::DependencyPaths::new(&ws.krates);
+
+        let app_id = app.krate.id.repr.as_str();
+        let dep_id = dep.krate.id.repr.as_str();
+
+        let bom: serde_json::Value =
+            serde_json::from_str(&render_json(&ws.krates, &paths, &nfos, &resolved).unwrap())
+                .unwrap();
+
+        assert_eq!(bom["bomFormat"], "CycloneDX");
+        assert_eq!(bom["specVersion"], SPEC_VERSION);
+        assert!(bom["serialNumber"]
+            .as_str()
+            .unwrap()
+            .starts_with("urn:uuid:"));
+
+        // The only workspace member is the subject of the bom
+        let subject = &bom["metadata"]["component"];
+        assert_eq!(subject["type"], "application");
+        assert_eq!(subject["bom-ref"], app_id);
+        assert_eq!(subject["purl"], "pkg:cargo/app@0.1.0");
+
+        let components = bom["components"].as_array().unwrap();
+        assert_eq!(components.len(), 1);
+        let component = &components[0];
+        assert_eq!(component["type"], "library");
+        assert_eq!(component["bom-ref"], dep_id);
+        assert_eq!(component["purl"], "pkg:cargo/dep@1.0.0");
+        assert_eq!(component["licenses"][0]["expression"], "MIT");
+        assert_eq!(
+            component["evidence"]["occurrences"],
+            serde_json::json!([{ "location": "LICENSE" }])
+        );
+        assert_eq!(component["evidence"]["licenses"][0]["license"]["id"], "MIT");
+
+        assert_eq!(
+            bom["dependencies"],
+            serde_json::json!([
+                { "ref": app_id, "dependsOn": [dep_id] },
+                { "ref": dep_id, "dependsOn": [] },
+            ])
+        );
+
+        let xml = render_xml(&ws.krates, &paths, &nfos, &resolved).unwrap();
+
+        assert_eq!(
+            children(&xml, "<bom "),
+            ["metadata", "components", "dependencies"]
+        );
+
+        // The schema uses sequences, so elements must be in schema order
+        let component = format!("<component type=\"library\" bom-ref=\"{}\">", dep_id);
+        assert_eq!(
+            children(&xml, &component),
+            [
+                "name",
+                "version",
+                "licenses",
+                "purl",
+                "properties",
+                "evidence"
+            ]
+        );
+        assert_eq!(
+            children(&xml[xml.find(&component).unwrap()..], "<evidence>"),
+            ["occurrences", "licenses"]
+        );
+
+        assert!(xml.contains(&format!(
+            "<dependency ref=\"{}\">\n      <dependency ref=\"{}\"/>\n    </dependency>",
+            app_id, dep_id
+        )));
+        assert!(xml.contains(&format!("<dependency ref=\"{}\"/>", dep_id)));
+        assert!(xml.contains("<purl>pkg:cargo/dep@1.0.0</purl>"));
+
+        assert!(xml.contains(
+            "<property name=\"cargo-about:notice\">Copyright &lt;Dep&gt; &amp; &quot;Friends&quot;</property>"
+        ));
+        assert!(!xml.contains("<Dep>"));
+    }
+}
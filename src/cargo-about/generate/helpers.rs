@@ -0,0 +1,298 @@
+//! The standard set of helpers that are available to every template, as well
+//! as user defined [rhai](https://rhai.rs) script helpers
+
+use anyhow::Context as _;
+use handlebars::{
+    handlebars_helper, Context, Handlebars, Helper, HelperDef, HelperResult, JsonValue, Output,
+    RenderContext, RenderError, ScopedJson,
+};
+use krates::{
+    semver::{Version, VersionReq},
+    Utf8Path as Path, Utf8PathBuf as PathBuf,
+};
+use std::{cmp::Ordering, collections::BTreeMap};
+
+/// The extension of script helpers in a template directory
+const SCRIPT_EXTENSION: &str = "rhai";
+
+/// Registers the standard helpers
+pub(super) fn register(reg: &mut Handlebars<'_>) {
+    reg.register_helper("json", Box::new(json));
+    reg.register_helper("group_by_license", Box::new(group_by_license));
+    reg.register_helper("slugify", Box::new(slugify));
+    reg.register_helper("truncate", Box::new(truncate));
+    reg.register_helper("indent", Box::new(indent));
+    reg.register_helper("join_authors", Box::new(join_authors));
+    reg.register_helper("semver_lt", Box::new(SemverCmp(Ordering::Less)));
+    reg.register_helper("semver_eq", Box::new(SemverCmp(Ordering::Equal)));
+    reg.register_helper("semver_gt", Box::new(SemverCmp(Ordering::Greater)));
+    reg.register_helper("semver_matches", Box::new(SemverMatches));
+}
+
+/// Registers every `.rhai` file in the template directory as a script helper,
+/// named after the file stem. Script helpers override standard helpers with
+/// the same name.
+pub(super) fn register_scripts(reg: &mut Handlebars<'_>, dir: &Path) -> anyhow::Result<()> {
+    for entry in std::fs::read_dir(dir)
+        .with_context(|| format!("unable to read template directory {}", dir))?
+    {
+        let path = PathBuf::try_from(entry?.path())?;
+
+        if path.extension() != Some(SCRIPT_EXTENSION) || !path.is_file() {
+            continue;
+        }
+
+        if let Some(name) = path.file_stem() {
+            reg.register_script_helper_file(name, &path)
+                .with_context(|| format!("unable to load script helper {}", path))?;
+            log::debug!("registered script helper '{}' from {}", name, path);
+        }
+    }
+
+    Ok(())
+}
+
+fn json(
+    h: &Helper<'_, '_>,
+    _r: &Handlebars<'_>,
+    _: &Context,
+    _rc: &mut RenderContext<'_, '_>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let param = h
+        .param(0)
+        .ok_or_else(|| RenderError::new("param not found"))?;
+
+    out.write(&serde_json::to_string_pretty(param.value())?)?;
+    Ok(())
+}
+
+handlebars_helper!(group_by_license: |crates: array| group_license(crates));
+// The text helpers treat missing values, eg. a crate without a description, as
+// empty text
+handlebars_helper!(slugify: |text: Json| slug(text.as_str().unwrap_or_default()));
+handlebars_helper!(truncate: |text: Json, len: u64, { ellipsis: str = "..." }| truncate_chars(text.as_str().unwrap_or_default(), len as usize, ellipsis));
+handlebars_helper!(indent: |text: Json, width: u64| indent_lines(text.as_str().unwrap_or_default(), width as usize));
+handlebars_helper!(join_authors: |authors: array, { separator: str = ", " }| join_names(authors, separator));
+
+/// Groups crates by their `license`, in license order
+fn group_license(crates: &[JsonValue]) -> JsonValue {
+    let mut groups = BTreeMap::<&str, Vec<&JsonValue>>::new();
+
+    for krate in crates {
+        let license = krate
+            .get("license")
+            .and_then(|l| l.as_str())
+            .unwrap_or_default();
+        groups.entry(license).or_default().push(krate);
+    }
+
+    groups
+        .into_iter()
+        .map(|(license, crates)| serde_json::json!({ "license": license, "crates": crates }))
+        .collect()
+}
+
+/// Lowercases the text and replaces every run of characters that aren't
+/// alphanumeric, `-`, or `_` with a single `-`, so that it can be used as eg.
+/// an HTML id
+fn slug(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+
+    slug
+}
+
+/// Truncates the text to at most `len` characters, appending the ellipsis if
+/// anything was removed
+fn truncate_chars(text: &str, len: usize, ellipsis: &str) -> String {
+    match text.char_indices().nth(len) {
+        Some((end, _)) => format!("{}{}", text[..end].trim_end(), ellipsis),
+        None => text.to_owned(),
+    }
+}
+
+/// Indents every non-empty line of the text by `width` spaces
+fn indent_lines(text: &str, width: usize) -> String {
+    let prefix = " ".repeat(width);
+
+    text.split('\n')
+        .map(|line| {
+            if line.trim().is_empty() {
+                line.to_owned()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Joins the names of the authors, without their email addresses, eg.
+/// `Jane Doe <jane@example.com>` becomes `Jane Doe`
+fn join_names(authors: &[JsonValue], separator: &str) -> String {
+    authors
+        .iter()
+        .filter_map(|author| author.as_str())
+        .map(|author| match author.find('<') {
+            Some(start) => author[..start].trim(),
+            None => author.trim(),
+        })
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn str_param<'a>(h: &'a Helper<'_, '_>, idx: usize) -> Result<&'a str, RenderError> {
+    h.param(idx)
+        .and_then(|p| p.value().as_str())
+        .ok_or_else(|| {
+            RenderError::new(format!(
+                "`{}` helper: parameter {} must be a string",
+                h.name(),
+                idx
+            ))
+        })
+}
+
+fn version_param(h: &Helper<'_, '_>, idx: usize) -> Result<Version, RenderError> {
+    let version = str_param(h, idx)?;
+    Version::parse(version).map_err(|err| {
+        RenderError::new(format!(
+            "`{}` helper: '{}' is not a valid version: {}",
+            h.name(),
+            version,
+            err
+        ))
+    })
+}
+
+/// Compares 2 versions, eg. `{{#if (semver_lt package.version "1.0.0")}}`
+struct SemverCmp(Ordering);
+
+impl HelperDef for SemverCmp {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        let lhs = version_param(h, 0)?;
+        let rhs = version_param(h, 1)?;
+
+        Ok(ScopedJson::Derived(JsonValue::from(
+            lhs.cmp(&rhs) == self.0,
+        )))
+    }
+}
+
+/// Checks if a version matches a requirement, eg.
+/// `{{#if (semver_matches package.version "^0.1")}}`
+struct SemverMatches;
+
+impl HelperDef for SemverMatches {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        let version = version_param(h, 0)?;
+        let req = str_param(h, 1)?;
+        let req = VersionReq::parse(req).map_err(|err| {
+            RenderError::new(format!(
+                "`{}` helper: '{}' is not a valid version requirement: {}",
+                h.name(),
+                req,
+                err
+            ))
+        })?;
+
+        Ok(ScopedJson::Derived(JsonValue::from(req.matches(&version))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, data: &JsonValue) -> String {
+        let mut reg = Handlebars::new();
+        register(&mut reg);
+        reg.render_template(template, data).unwrap()
+    }
+
+    #[test]
+    fn formats_text() {
+        assert_eq!(
+            slug("Apache-2.0 WITH LLVM-exception"),
+            "apache-2-0-with-llvm-exception"
+        );
+        assert_eq!(slug("  (MIT OR Apache-2.0) "), "mit-or-apache-2-0");
+
+        assert_eq!(truncate_chars("short", 5, "..."), "short");
+        assert_eq!(truncate_chars("a little longer", 8, "…"), "a little…");
+
+        assert_eq!(indent_lines("one\n\ntwo\n", 2), "  one\n\n  two\n");
+
+        assert_eq!(
+            join_names(
+                &[json!("Jane Doe <jane@example.com>"), json!("John Doe")],
+                " & "
+            ),
+            "Jane Doe & John Doe"
+        );
+    }
+
+    #[test]
+    fn groups_by_license() {
+        let data = json!({
+            "crates": [
+                { "package": { "name": "b" }, "license": "MIT" },
+                { "package": { "name": "a" }, "license": "Apache-2.0" },
+                { "package": { "name": "c" }, "license": "MIT" },
+            ]
+        });
+
+        assert_eq!(
+            render(
+                "{{#each (group_by_license crates)}}{{license}}:{{#each crates}} {{package.name}}{{/each}};{{/each}}",
+                &data
+            ),
+            "Apache-2.0: a;MIT: b c;"
+        );
+    }
+
+    #[test]
+    fn compares_versions() {
+        let data = json!({ "version": "0.10.1" });
+
+        assert_eq!(
+            render(
+                r#"{{semver_lt version "0.9.0"}} {{semver_gt version "0.9.0"}} {{semver_eq version "0.10.1"}} {{semver_matches version "^0.10"}}"#,
+                &data
+            ),
+            "false true true true"
+        );
+
+        let mut reg = Handlebars::new();
+        register(&mut reg);
+        assert!(reg
+            .render_template(r#"{{semver_lt version "1"}}"#, &data)
+            .is_err());
+    }
+}
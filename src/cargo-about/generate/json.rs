@@ -0,0 +1,367 @@
+//! A stable, versioned JSON representation of the gathered license information
+//! that can be consumed by other tools without needing to maintain a template
+
+use cargo_about::licenses::{self, copyright::Copyright, LicenseFileKind, LicenseInfo, Provenance};
+use krates::Utf8Path as Path;
+use serde::Serialize;
+
+/// The version of the schema, this is bumped any time a breaking change is
+/// made to the output
+const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum FileKind {
+    /// The file is the full text of a license
+    Text,
+    /// The file is the full text of a license that applies to a subdirectory
+    Addendum,
+    /// The file only contains a license header
+    Header,
+}
+
+#[derive(Serialize)]
+struct File<'a> {
+    /// The path of the file, relative to the crate root if it is in the crate
+    path: &'a Path,
+    /// The license expression detected for the file
+    license: &'a str,
+    /// The confidence of the detection, 0.0 - 1.0
+    confidence: f32,
+    kind: FileKind,
+    /// The byte range of the license text, if it is only part of the file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    range: Option<&'a std::ops::Range<usize>>,
+    /// Where the license information for the file was determined
+    provenance: &'a Provenance,
+}
+
+#[derive(Serialize)]
+struct Crate<'a> {
+    name: &'a str,
+    version: String,
+    /// The unique package id
+    id: &'a str,
+    source: Option<String>,
+    repository: Option<&'a str>,
+    /// The license field as declared in the crate's manifest
+    declared: Option<&'a str>,
+    /// The effective license expression for the crate, after any
+    /// clarifications or workarounds are applied, or synthesized from its
+    /// license files if it didn't declare one
+    expression: Option<String>,
+    /// Where the license information for the crate was determined
+    provenance: &'a Provenance,
+    /// The minimal set of licenses that satisfy the expression, according to
+    /// the accepted licenses
+    requirements: Vec<String>,
+    /// The files that license information was gathered from
+    files: Vec<File<'a>>,
+    /// The copyright statements in the crate's license files
+    copyrights: &'a [Copyright],
+    /// Indices into the top level `licenses` that apply to this crate
+    licenses: Vec<usize>,
+    /// Indices into the top level `notices` that apply to this crate
+    notices: Vec<usize>,
+    /// The shortest dependency path, as crate ids, from each workspace member
+    /// that depends on this crate
+    dependency_paths: Vec<Vec<&'a str>>,
+}
+
+#[derive(Serialize)]
+struct License<'a> {
+    /// The SPDX short identifier for the license
+    id: &'a str,
+    /// The full name of the license
+    name: &'a str,
+    /// The full license text
+    text: &'a str,
+    /// The path where the license text was sourced from, if it's not the
+    /// canonical text
+    source_path: Option<&'a Path>,
+    /// The copyright statements in the license text
+    copyrights: &'a [Copyright],
+    /// The ids of the crates this license text applies to
+    used_by: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Notice<'a> {
+    /// The path of the NOTICE file, relative to the crate root
+    path: &'a Path,
+    /// The full text of the NOTICE file
+    text: &'a str,
+    /// The ids of the crates this NOTICE applies to
+    used_by: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Output<'a> {
+    version: u32,
+    crates: Vec<Crate<'a>>,
+    licenses: Vec<License<'a>>,
+    notices: Vec<Notice<'a>>,
+}
+
+pub(super) fn render(
+    nfos: &[licenses::KrateLicense<'_>],
+    resolved: &[Option<licenses::Resolved>],
+    paths: &cargo_about::DependencyPaths<'_>,
+    input: &super::Input<'_>,
+) -> anyhow::Result<String> {
+    let licenses: Vec<_> = input
+        .licenses
+        .iter()
+        .map(|lic| License {
+            id: &lic.id,
+            name: &lic.name,
+            text: &lic.text,
+            source_path: lic.source_path.as_deref(),
+            copyrights: &lic.copyrights,
+            used_by: lic
+                .used_by
+                .iter()
+                .map(|ub| ub.krate.id.repr.as_str())
+                .collect(),
+        })
+        .collect();
+
+    let notices: Vec<_> = input
+        .notices
+        .iter()
+        .map(|notice| Notice {
+            path: &notice.path,
+            text: &notice.text,
+            used_by: notice
+                .used_by
+                .iter()
+                .map(|ub| ub.krate.id.repr.as_str())
+                .collect(),
+        })
+        .collect();
+
+    let crates = nfos
+        .iter()
+        .zip(resolved.iter())
+        .filter(|(nfo, _)| !matches!(nfo.lic_info, LicenseInfo::Ignore))
+        .map(|(nfo, resolved)| {
+            let krate = nfo.krate;
+            let root = krate.manifest_path.parent();
+
+            let files = nfo
+                .license_files
+                .iter()
+                .map(|lf| File {
+                    path: root
+                        .and_then(|root| lf.path.strip_prefix(root).ok())
+                        .unwrap_or(&lf.path),
+                    license: lf.license_expr.as_ref(),
+                    confidence: lf.confidence,
+                    kind: match &lf.kind {
+                        LicenseFileKind::Text(_) => FileKind::Text,
+                        LicenseFileKind::AddendumText(..) => FileKind::Addendum,
+                        LicenseFileKind::Header => FileKind::Header,
+                    },
+                    range: lf.range.as_ref(),
+                    provenance: &lf.provenance,
+                })
+                .collect();
+
+            let licenses = licenses
+                .iter()
+                .enumerate()
+                .filter(|(_, lic)| lic.used_by.contains(&krate.id.repr.as_str()))
+                .map(|(i, _)| i)
+                .collect();
+
+            let notices = notices
+                .iter()
+                .enumerate()
+                .filter(|(_, notice)| notice.used_by.contains(&krate.id.repr.as_str()))
+                .map(|(i, _)| i)
+                .collect();
+
+            Crate {
+                name: &krate.name,
+                version: krate.version.to_string(),
+                id: &krate.id.repr,
+                source: krate.source.as_ref().map(|src| src.to_string()),
+                repository: krate.repository.as_deref(),
+                declared: krate.license.as_deref(),
+                expression: match &nfo.lic_info {
+                    LicenseInfo::Expr(expr) => Some(expr.to_string()),
+                    LicenseInfo::Unknown => resolved
+                        .as_ref()
+                        .and_then(|res| res.expression.as_ref())
+                        .map(|expr| expr.to_string()),
+                    LicenseInfo::Ignore => None,
+                },
+                provenance: &nfo.provenance,
+                requirements: resolved
+                    .iter()
+                    .flat_map(|res| res.licenses.iter().map(|req| req.to_string()))
+                    .collect(),
+                files,
+                copyrights: &nfo.copyrights,
+                licenses,
+                notices,
+                dependency_paths: paths
+                    .paths(&krate.id)
+                    .into_iter()
+                    .map(|path| {
+                        path.into_iter()
+                            .map(|krate| krate.id.repr.as_str())
+                            .collect()
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let output = Output {
+        version: SCHEMA_VERSION,
+        crates,
+        licenses,
+        notices,
+    };
+
+    Ok(serde_json::to_string_pretty(&output)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generate::test::{license_file, Workspace};
+    use serde_json::Value;
+
+    fn keys(value: &Value) -> Vec<&str> {
+        let mut keys: Vec<_> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    #[test]
+    fn renders_stable_schema() {
+        let ws = Workspace::new("json");
+        let app = ws
+            .krates
+            .krates()
+            .find(|kn| kn.krate.name == "app")
+            .unwrap();
+        let dep = ws
+            .krates
+            .krates()
+            .find(|kn| kn.krate.name == "dep")
+            .unwrap();
+
+        let mut apache = license_file(ws.root.join("dep/LICENSE-APACHE"), "apache license");
+        apache.license_expr = ::spdx::Expression::parse("Apache-2.0").unwrap();
+
+        let nfos = vec![
+            licenses::KrateLicense {
+                krate: &app.krate,
+                lic_info: LicenseInfo::Expr(::spdx::Expression::parse("MIT").unwrap()),
+                provenance: Provenance::FileSystem,
+                license_files: vec![license_file(ws.root.join("app/LICENSE"), "app license")],
+                notices: Vec::new(),
+                copyrights: Vec::new(),
+            },
+            // The license expression of crates that don't declare one is
+            // synthesized from their license files
+            licenses::KrateLicense {
+                krate: &dep.krate,
+                lic_info: LicenseInfo::Unknown,
+                provenance: Provenance::FileSystem,
+                license_files: vec![
+                    license_file(ws.root.join("dep/LICENSE"), "dep license"),
+                    apache,
+                ],
+                notices: vec![licenses::Notice {
+                    path: "NOTICE".into(),
+                    text: "dep notice".to_owned(),
+                }],
+                copyrights: Vec::new(),
+            },
+        ];
+
+        let cfg: licenses::config::Config =
+            toml::from_str(r#"accepted = ["MIT", "Apache-2.0"]"#).unwrap();
+        let (_, resolved) = licenses::resolution::resolve(&nfos, &cfg);
+        let paths = cargo_about::DependencyPaths::new(&ws.krates);
+        let input = super::super::generate(&nfos, &resolved, &paths);
+
+        let output: Value =
+            serde_json::from_str(&render(&nfos, &resolved, &paths, &input).unwrap()).unwrap();
+
+        // Any change to these is a breaking change that requires bumping the
+        // schema version
+        assert_eq!(keys(&output), ["crates", "licenses", "notices", "version"]);
+        assert_eq!(output["version"], SCHEMA_VERSION);
+        assert_eq!(
+            keys(&output["crates"][0]),
+            [
+                "copyrights",
+                "declared",
+                "dependency_paths",
+                "expression",
+                "files",
+                "id",
+                "licenses",
+                "name",
+                "notices",
+                "provenance",
+                "repository",
+                "requirements",
+                "source",
+                "version",
+            ]
+        );
+        assert_eq!(
+            keys(&output["crates"][0]["files"][0]),
+            ["confidence", "kind", "license", "path", "provenance"]
+        );
+        assert_eq!(
+            keys(&output["licenses"][0]),
+            ["copyrights", "id", "name", "source_path", "text", "used_by"]
+        );
+        assert_eq!(keys(&output["notices"][0]), ["path", "text", "used_by"]);
+
+        let app_id = app.krate.id.repr.as_str();
+        let dep_id = dep.krate.id.repr.as_str();
+
+        let crates = output["crates"].as_array().unwrap();
+        assert_eq!(crates[0]["id"], app_id);
+        assert_eq!(crates[0]["expression"], "MIT");
+        assert_eq!(crates[0]["files"][0]["path"], "LICENSE");
+        assert_eq!(crates[0]["files"][0]["kind"], "text");
+        assert_eq!(crates[0]["provenance"]["kind"], "file-system");
+        assert_eq!(crates[0]["dependency_paths"], serde_json::json!([[app_id]]));
+
+        assert_eq!(crates[1]["id"], dep_id);
+        assert_eq!(crates[1]["declared"], "MIT");
+        assert_eq!(crates[1]["expression"], "(Apache-2.0) AND (MIT)");
+        assert_eq!(
+            crates[1]["requirements"],
+            serde_json::json!(["MIT", "Apache-2.0"])
+        );
+        assert_eq!(
+            crates[1]["dependency_paths"],
+            serde_json::json!([[app_id, dep_id]])
+        );
+        assert_eq!(crates[1]["notices"], serde_json::json!([0]));
+
+        let licenses = output["licenses"].as_array().unwrap();
+        assert_eq!(licenses.len(), 3);
+        assert_eq!(licenses[0]["id"], "Apache-2.0");
+        assert_eq!(licenses[0]["used_by"], serde_json::json!([dep_id]));
+        assert_eq!(crates[1]["licenses"], serde_json::json!([0, 2]));
+
+        assert_eq!(output["notices"][0]["text"], "dep notice");
+        assert_eq!(output["notices"][0]["used_by"], serde_json::json!([dep_id]));
+    }
+}
@@ -0,0 +1,890 @@
+//! Exports the gathered license information as an
+//! [SPDX 2.3](https://spdx.github.io/spdx-spec/v2.3/) document, in either the
+//! JSON or tag-value serialization
+
+use anyhow::Context as _;
+use cargo_about::{
+    licenses::{self, LicenseFileKind, LicenseInfo},
+    Krate, Krates,
+};
+use krates::Utf8Path as Path;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Write as _,
+};
+
+const NOASSERTION: &str = "NOASSERTION";
+const DOCUMENT_ID: &str = "SPDXRef-DOCUMENT";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreationInfo {
+    created: String,
+    creators: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExternalRef {
+    reference_category: &'static str,
+    reference_type: &'static str,
+    reference_locator: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Package {
+    name: String,
+    #[serde(rename = "SPDXID")]
+    spdx_id: String,
+    version_info: String,
+    download_location: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    homepage: Option<String>,
+    /// Only registry crates, whose files are exactly those of the published
+    /// package, are analyzed, any other crate is only described by its metadata
+    files_analyzed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    package_verification_code: Option<VerificationCode>,
+    /// The licenses found in the package's files, only present if the files
+    /// were analyzed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    license_info_from_files: Vec<String>,
+    license_concluded: String,
+    license_declared: String,
+    copyright_text: String,
+    /// The contents of the package's NOTICE files, which need to be
+    /// reproduced along with the package
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attribution_texts: Vec<String>,
+    /// Where the license information for the package was determined
+    comment: String,
+    external_refs: Vec<ExternalRef>,
+    /// The ids of the license files contained by the package
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    has_files: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct VerificationCode {
+    #[serde(rename = "packageVerificationCodeValue")]
+    value: String,
+}
+
+#[derive(Serialize)]
+struct Checksum {
+    algorithm: &'static str,
+    #[serde(rename = "checksumValue")]
+    value: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct File {
+    file_name: String,
+    #[serde(rename = "SPDXID")]
+    spdx_id: String,
+    checksums: Vec<Checksum>,
+    license_concluded: String,
+    license_info_in_files: Vec<String>,
+    copyright_text: String,
+    /// Where the license information for the file was determined
+    comment: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExtractedLicense {
+    license_id: String,
+    name: String,
+    extracted_text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Relationship {
+    spdx_element_id: String,
+    relationship_type: &'static str,
+    related_spdx_element: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+    spdx_version: &'static str,
+    data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    spdx_id: &'static str,
+    name: String,
+    document_namespace: String,
+    creation_info: CreationInfo,
+    packages: Vec<Package>,
+    files: Vec<File>,
+    has_extracted_licensing_infos: Vec<ExtractedLicense>,
+    relationships: Vec<Relationship>,
+}
+
+/// SPDX identifiers may only contain letters, numbers, `.` and `-`
+fn sanitize_id(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+fn download_location(krate: &Krate) -> String {
+    match &krate.source {
+        Some(src) if src.is_crates_io() => format!(
+            "https://crates.io/api/v1/crates/{}/{}/download",
+            krate.name, krate.version
+        ),
+        Some(src) => match src.repr.strip_prefix("git+") {
+            // git+https://github.com/owner/repo?branch=main#<rev> -> git+https://github.com/owner/repo@<rev>
+            Some(git) => {
+                let (url, rev) = git.split_once('#').unwrap_or((git, ""));
+                let url = url.split_once('?').map_or(url, |(url, _)| url);
+
+                if rev.is_empty() {
+                    format!("git+{}", url)
+                } else {
+                    format!("git+{}@{}", url, rev)
+                }
+            }
+            None => NOASSERTION.to_owned(),
+        },
+        None => NOASSERTION.to_owned(),
+    }
+}
+
+/// Gets the identifier of the license, as it should appear in the document
+fn license_id(item: &spdx::LicenseItem) -> String {
+    match item {
+        spdx::LicenseItem::Spdx { id, .. } => id.name.to_owned(),
+        spdx::LicenseItem::Other {
+            doc_ref: Some(doc_ref),
+            lic_ref,
+        } => format!("DocumentRef-{}:LicenseRef-{}", doc_ref, lic_ref),
+        spdx::LicenseItem::Other { lic_ref, .. } => format!("LicenseRef-{}", lic_ref),
+    }
+}
+
+/// The copyright statements, one per line, or `NOASSERTION` if there are none
+fn copyright_text(copyrights: &[licenses::copyright::Copyright]) -> String {
+    if copyrights.is_empty() {
+        return NOASSERTION.to_owned();
+    }
+
+    copyrights
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Multi-line values in the tag-value format must be wrapped in `<text>` tags
+fn text_value(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains('\n') {
+        format!("<text>{}</text>", value).into()
+    } else {
+        value.into()
+    }
+}
+
+/// Whether the crate was retrieved from a registry, in which case the files in
+/// its root are exactly the files of the published package
+fn is_registry(krate: &Krate) -> bool {
+    krate.source.as_ref().map_or(false, |src| {
+        src.repr.starts_with("registry+") || src.repr.starts_with("sparse+")
+    })
+}
+
+/// Computes the package verification code, the SHA1 of the sorted SHA1s of
+/// every file in the package
+fn verification_code(root: &Path) -> anyhow::Result<String> {
+    use ring::digest;
+
+    let mut sha1s = Vec::new();
+    for entry in ignore::WalkBuilder::new(root)
+        .standard_filters(false)
+        .build()
+    {
+        let entry = entry?;
+        // Cargo marks the package as successfully unpacked with this file, it
+        // isn't a part of the package itself
+        if !entry.file_type().map_or(false, |ft| ft.is_file())
+            || (entry.depth() == 1 && entry.file_name() == ".cargo-ok")
+        {
+            continue;
+        }
+
+        let contents = std::fs::read(entry.path())
+            .with_context(|| format!("unable to read '{}'", entry.path().display()))?;
+        sha1s.push(cargo_about::to_hex(
+            digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &contents).as_ref(),
+        ));
+    }
+
+    sha1s.sort();
+
+    Ok(cargo_about::to_hex(
+        digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, sha1s.concat().as_bytes()).as_ref(),
+    ))
+}
+
+fn checksums(path: &Path) -> anyhow::Result<Vec<Checksum>> {
+    use ring::digest;
+
+    let contents = std::fs::read(path).with_context(|| format!("unable to read '{}'", path))?;
+
+    Ok([
+        ("SHA1", &digest::SHA1_FOR_LEGACY_USE_ONLY),
+        ("SHA256", &digest::SHA256),
+    ]
+    .into_iter()
+    .map(|(algorithm, algo)| Checksum {
+        algorithm,
+        value: cargo_about::to_hex(digest::digest(algo, &contents).as_ref()),
+    })
+    .collect())
+}
+
+impl Document {
+    fn new(
+        krates: &Krates,
+        paths: &cargo_about::DependencyPaths<'_>,
+        nfos: &[licenses::KrateLicense<'_>],
+        resolved: &[Option<licenses::Resolved>],
+    ) -> Self {
+        let name = {
+            let mut roots = paths.roots();
+            match (roots.next(), roots.next()) {
+                (Some(root), None) => root.name.clone(),
+                _ => krates
+                    .lock_path()
+                    .parent()
+                    .and_then(|root| root.file_name())
+                    .unwrap_or("workspace")
+                    .to_owned(),
+            }
+        };
+
+        let mut packages = Vec::new();
+        let mut files = Vec::new();
+        let mut relationships = Vec::new();
+        let mut extracted = BTreeMap::new();
+        let mut ids = HashMap::new();
+        let mut used_ids = HashSet::new();
+
+        for (nfo, resolved) in nfos
+            .iter()
+            .zip(resolved.iter())
+            .filter(|(nfo, _)| !matches!(nfo.lic_info, LicenseInfo::Ignore))
+        {
+            let krate = nfo.krate;
+
+            let spdx_id = {
+                let base =
+                    sanitize_id(&format!("SPDXRef-Package-{}-{}", krate.name, krate.version));
+                let mut spdx_id = base.clone();
+                let mut i = 1;
+                while !used_ids.insert(spdx_id.clone()) {
+                    spdx_id = format!("{}-{}", base, i);
+                    i += 1;
+                }
+                spdx_id
+            };
+
+            let license_declared = super::declared_license(krate)
+                .map_or_else(|| NOASSERTION.to_owned(), |expr| expr.to_string());
+
+            let license_concluded = match resolved {
+                Some(res) if !res.licenses.is_empty() => res
+                    .licenses
+                    .iter()
+                    .map(|req| req.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" AND "),
+                _ => NOASSERTION.to_owned(),
+            };
+
+            // Only the files in the package can be contained by it, ie. not
+            // files retrieved from git repositories
+            let root = krate.manifest_path.parent().unwrap();
+            let verification_code = if is_registry(krate) {
+                match verification_code(root) {
+                    Ok(code) => Some(code),
+                    Err(e) => {
+                        log::warn!("unable to analyze the files of '{}': {:#}", krate, e);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+
+            // A single file can contain multiple licenses, each of which is
+            // its own license file
+            let mut license_files = Vec::<(_, Vec<_>)>::new();
+            for lf in nfo
+                .license_files
+                .iter()
+                .filter(|lf| verification_code.is_some() && lf.path.starts_with(root))
+            {
+                match license_files.iter_mut().find(|(path, _)| *path == &lf.path) {
+                    Some((_, lfs)) => lfs.push(lf),
+                    None => license_files.push((&lf.path, vec![lf])),
+                }
+            }
+
+            let mut has_files = Vec::new();
+            let mut license_info_from_files = BTreeSet::new();
+
+            for (i, (path, lfs)) in license_files.into_iter().enumerate() {
+                let checksums = match checksums(path) {
+                    Ok(checksums) => checksums,
+                    Err(e) => {
+                        log::warn!(
+                            "omitting '{}' for '{}' from the document: {:#}",
+                            path,
+                            krate,
+                            e
+                        );
+                        continue;
+                    }
+                };
+
+                let file_name = path.strip_prefix(root).unwrap_or(path);
+
+                let license_info_in_files: Vec<_> = lfs
+                    .iter()
+                    .flat_map(|lf| lf.license_expr.requirements())
+                    .map(|er| license_id(&er.req.license))
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect();
+                license_info_from_files.extend(license_info_in_files.iter().cloned());
+                let license_concluded = if lfs.len() == 1 {
+                    lfs[0].license_expr.to_string()
+                } else {
+                    lfs.iter()
+                        .map(|lf| {
+                            if lf.license_expr.requirements().count() > 1 {
+                                format!("({})", lf.license_expr)
+                            } else {
+                                lf.license_expr.to_string()
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" AND ")
+                };
+
+                let mut provenances = Vec::new();
+                for lf in &lfs {
+                    let provenance = lf.provenance.to_string();
+                    if !provenances.contains(&provenance) {
+                        provenances.push(provenance);
+                    }
+                }
+
+                let file_id = format!("{}-File-{}", spdx_id.replacen("-Package-", "-", 1), i);
+
+                relationships.push(Relationship {
+                    spdx_element_id: spdx_id.clone(),
+                    relationship_type: "CONTAINS",
+                    related_spdx_element: file_id.clone(),
+                });
+                has_files.push(file_id.clone());
+
+                files.push(File {
+                    file_name: format!("./{}", file_name),
+                    spdx_id: file_id,
+                    checksums,
+                    license_concluded,
+                    license_info_in_files,
+                    copyright_text: copyright_text(&licenses::copyright::merge(
+                        lfs.iter().flat_map(|lf| nfo.license_file_copyrights(lf)),
+                    )),
+                    comment: format!(
+                        "License information determined by {}",
+                        provenances.join(", ")
+                    ),
+                });
+            }
+
+            // Any license that isn't on the SPDX list needs to have its text
+            // included in the document
+            let expressions = match &nfo.lic_info {
+                LicenseInfo::Expr(expr) => Some(expr),
+                _ => None,
+            }
+            .into_iter()
+            .chain(nfo.license_files.iter().map(|lf| &lf.license_expr));
+
+            for expr in expressions {
+                for er in expr.requirements() {
+                    let lic_ref = match &er.req.license {
+                        spdx::LicenseItem::Other {
+                            doc_ref: None,
+                            lic_ref,
+                        } => lic_ref,
+                        _ => continue,
+                    };
+
+                    if extracted.contains_key(lic_ref) {
+                        continue;
+                    }
+
+                    let text = nfo.license_files.iter().find_map(|lf| {
+                        let matches = lf.license_expr.requirements().any(|fer| {
+                            matches!(&fer.req.license, spdx::LicenseItem::Other { lic_ref: flr, .. } if flr == lic_ref)
+                        });
+
+                        match &lf.kind {
+                            LicenseFileKind::Text(text) | LicenseFileKind::AddendumText(text, _)
+                                if matches =>
+                            {
+                                Some(text.clone())
+                            }
+                            _ => None,
+                        }
+                    });
+
+                    extracted.insert(
+                        lic_ref.clone(),
+                        ExtractedLicense {
+                            license_id: format!("LicenseRef-{}", lic_ref),
+                            name: lic_ref.clone(),
+                            extracted_text: text.unwrap_or_else(|| NOASSERTION.to_owned()),
+                        },
+                    );
+                }
+            }
+
+            packages.push(Package {
+                name: krate.name.clone(),
+                spdx_id: spdx_id.clone(),
+                version_info: krate.version.to_string(),
+                download_location: download_location(krate),
+                homepage: krate.homepage.clone(),
+                files_analyzed: verification_code.is_some(),
+                license_info_from_files: match verification_code {
+                    Some(_) if license_info_from_files.is_empty() => vec![NOASSERTION.to_owned()],
+                    Some(_) => license_info_from_files.into_iter().collect(),
+                    None => Vec::new(),
+                },
+                package_verification_code: verification_code
+                    .map(|value| VerificationCode { value }),
+                license_concluded,
+                license_declared,
+                copyright_text: copyright_text(&nfo.copyrights),
+                attribution_texts: nfo.notices.iter().map(|n| n.text.clone()).collect(),
+                comment: format!("License information determined by {}", nfo.provenance),
+                external_refs: vec![ExternalRef {
+                    reference_category: "PACKAGE-MANAGER",
+                    reference_type: "purl",
+                    reference_locator: format!("pkg:cargo/{}@{}", krate.name, krate.version),
+                }],
+                has_files,
+            });
+
+            ids.insert(&krate.id, spdx_id);
+        }
+
+        // The roots, normally the workspace members, are the subject of the
+        // document, but they may have been ignored, in which case their direct
+        // dependencies are described
+        for root in paths.roots() {
+            let described: Vec<_> = match ids.get(&root.id) {
+                Some(id) => vec![id.clone()],
+                None => krates
+                    .nid_for_kid(&root.id)
+                    .into_iter()
+                    .flat_map(|nid| krates.get_deps(nid))
+                    .filter_map(|(dep, _)| ids.get(&dep.id).cloned())
+                    .collect(),
+            };
+
+            for id in described {
+                if !relationships.iter().any(|rel: &Relationship| {
+                    rel.relationship_type == "DESCRIBES" && rel.related_spdx_element == id
+                }) {
+                    relationships.push(Relationship {
+                        spdx_element_id: DOCUMENT_ID.to_owned(),
+                        relationship_type: "DESCRIBES",
+                        related_spdx_element: id,
+                    });
+                }
+            }
+        }
+
+        for nfo in nfos {
+            let (from, nid) = match (ids.get(&nfo.krate.id), krates.nid_for_kid(&nfo.krate.id)) {
+                (Some(from), Some(nid)) => (from, nid),
+                _ => continue,
+            };
+
+            // A crate can depend on the same crate multiple times, eg. for
+            // different targets, but we only care about the kind of dependency
+            let deps: BTreeSet<_> = krates
+                .get_deps(nid)
+                .filter_map(|(dep, edge)| {
+                    let to = ids.get(&dep.id)?;
+                    Some(match edge.kind {
+                        krates::DepKind::Normal => (from, "DEPENDS_ON", to),
+                        krates::DepKind::Build => (to, "BUILD_DEPENDENCY_OF", from),
+                        krates::DepKind::Dev => (to, "DEV_DEPENDENCY_OF", from),
+                    })
+                })
+                .collect();
+
+            relationships.extend(
+                deps.into_iter()
+                    .map(|(a, relationship_type, b)| Relationship {
+                        spdx_element_id: a.clone(),
+                        relationship_type,
+                        related_spdx_element: b.clone(),
+                    }),
+            );
+        }
+
+        let created = super::timestamp();
+
+        // The namespace must be unique for every document, but two documents
+        // created at the same time for the same crates are the same document
+        let document_namespace = {
+            use std::hash::Hasher;
+            let mut hasher = twox_hash::XxHash64::default();
+
+            hasher.write(created.as_bytes());
+            for pkg in &packages {
+                hasher.write(pkg.spdx_id.as_bytes());
+            }
+
+            format!(
+                "https://spdx.org/spdxdocs/{}-{:016x}",
+                sanitize_id(&name),
+                hasher.finish()
+            )
+        };
+
+        Self {
+            spdx_version: "SPDX-2.3",
+            data_license: "CC0-1.0",
+            spdx_id: DOCUMENT_ID,
+            name,
+            document_namespace,
+            creation_info: CreationInfo {
+                created,
+                creators: vec![format!("Tool: cargo-about-{}", env!("CARGO_PKG_VERSION"))],
+            },
+            packages,
+            files,
+            has_extracted_licensing_infos: extracted.into_values().collect(),
+            relationships,
+        }
+    }
+
+    fn to_tag_value(&self) -> Result<String, std::fmt::Error> {
+        let mut out = String::new();
+
+        writeln!(out, "SPDXVersion: {}", self.spdx_version)?;
+        writeln!(out, "DataLicense: {}", self.data_license)?;
+        writeln!(out, "SPDXID: {}", self.spdx_id)?;
+        writeln!(out, "DocumentName: {}", self.name)?;
+        writeln!(out, "DocumentNamespace: {}", self.document_namespace)?;
+        for creator in &self.creation_info.creators {
+            writeln!(out, "Creator: {}", creator)?;
+        }
+        writeln!(out, "Created: {}", self.creation_info.created)?;
+
+        for pkg in &self.packages {
+            writeln!(out)?;
+            writeln!(out, "PackageName: {}", pkg.name)?;
+            writeln!(out, "SPDXID: {}", pkg.spdx_id)?;
+            writeln!(out, "PackageVersion: {}", pkg.version_info)?;
+            writeln!(out, "PackageDownloadLocation: {}", pkg.download_location)?;
+            if let Some(homepage) = &pkg.homepage {
+                writeln!(out, "PackageHomePage: {}", homepage)?;
+            }
+            writeln!(out, "FilesAnalyzed: {}", pkg.files_analyzed)?;
+            if let Some(code) = &pkg.package_verification_code {
+                writeln!(out, "PackageVerificationCode: {}", code.value)?;
+            }
+            for lic in &pkg.license_info_from_files {
+                writeln!(out, "PackageLicenseInfoFromFiles: {}", lic)?;
+            }
+            writeln!(out, "PackageLicenseConcluded: {}", pkg.license_concluded)?;
+            writeln!(out, "PackageLicenseDeclared: {}", pkg.license_declared)?;
+            writeln!(
+                out,
+                "PackageCopyrightText: {}",
+                text_value(&pkg.copyright_text)
+            )?;
+            for text in &pkg.attribution_texts {
+                writeln!(out, "PackageAttributionText: <text>{}</text>", text)?;
+            }
+            writeln!(out, "PackageComment: {}", text_value(&pkg.comment))?;
+            for er in &pkg.external_refs {
+                writeln!(
+                    out,
+                    "ExternalRef: {} {} {}",
+                    er.reference_category, er.reference_type, er.reference_locator
+                )?;
+            }
+
+            // Files directly follow the package that contains them
+            for file in self
+                .files
+                .iter()
+                .filter(|file| pkg.has_files.contains(&file.spdx_id))
+            {
+                writeln!(out)?;
+                writeln!(out, "FileName: {}", file.file_name)?;
+                writeln!(out, "SPDXID: {}", file.spdx_id)?;
+                for checksum in &file.checksums {
+                    writeln!(
+                        out,
+                        "FileChecksum: {}: {}",
+                        checksum.algorithm, checksum.value
+                    )?;
+                }
+                writeln!(out, "LicenseConcluded: {}", file.license_concluded)?;
+                for lic in &file.license_info_in_files {
+                    writeln!(out, "LicenseInfoInFile: {}", lic)?;
+                }
+                writeln!(
+                    out,
+                    "FileCopyrightText: {}",
+                    text_value(&file.copyright_text)
+                )?;
+                writeln!(out, "FileComment: {}", text_value(&file.comment))?;
+            }
+        }
+
+        for lic in &self.has_extracted_licensing_infos {
+            writeln!(out)?;
+            writeln!(out, "LicenseID: {}", lic.license_id)?;
+            // Multi-line values must be wrapped in `<text>` tags
+            writeln!(out, "ExtractedText: <text>{}</text>", lic.extracted_text)?;
+            writeln!(out, "LicenseName: {}", lic.name)?;
+        }
+
+        writeln!(out)?;
+        for rel in &self.relationships {
+            writeln!(
+                out,
+                "Relationship: {} {} {}",
+                rel.spdx_element_id, rel.relationship_type, rel.related_spdx_element
+            )?;
+        }
+
+        Ok(out)
+    }
+}
+
+/// Renders an SPDX document in the JSON serialization
+pub(super) fn render_json(
+    krates: &Krates,
+    paths: &cargo_about::DependencyPaths<'_>,
+    nfos: &[licenses::KrateLicense<'_>],
+    resolved: &[Option<licenses::Resolved>],
+) -> anyhow::Result<String> {
+    let doc = Document::new(krates, paths, nfos, resolved);
+    Ok(serde_json::to_string_pretty(&doc)?)
+}
+
+/// Renders an SPDX document in the tag-value serialization
+pub(super) fn render_tag_value(
+    krates: &Krates,
+    paths: &cargo_about::DependencyPaths<'_>,
+    nfos: &[licenses::KrateLicense<'_>],
+    resolved: &[Option<licenses::Resolved>],
+) -> anyhow::Result<String> {
+    let doc = Document::new(krates, paths, nfos, resolved);
+    Ok(doc.to_tag_value()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generate::test::{license_file, Workspace};
+
+    fn sha1(contents: &str) -> String {
+        use ring::digest;
+        cargo_about::to_hex(
+            digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, contents.as_bytes()).as_ref(),
+        )
+    }
+
+    /// Checks the rules of the SPDX 2.3 specification that apply to the parts
+    /// of the document we generate
+    fn validate(doc: &serde_json::Value) {
+        let ids: HashSet<_> = std::iter::once(DOCUMENT_ID)
+            .chain(
+                doc["packages"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|p| p["SPDXID"].as_str().unwrap()),
+            )
+            .chain(
+                doc["files"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|f| f["SPDXID"].as_str().unwrap()),
+            )
+            .collect();
+        assert_eq!(
+            ids.len(),
+            1 + doc["packages"].as_array().unwrap().len() + doc["files"].as_array().unwrap().len(),
+            "SPDX identifiers must be unique"
+        );
+
+        for rel in doc["relationships"].as_array().unwrap() {
+            for element in ["spdxElementId", "relatedSpdxElement"] {
+                assert!(
+                    ids.contains(rel[element].as_str().unwrap()),
+                    "{} is unknown",
+                    rel
+                );
+            }
+        }
+
+        let is_hex = |value: &serde_json::Value, len| {
+            value.as_str().map_or(false, |v| {
+                v.len() == len && v.chars().all(|c| c.is_ascii_hexdigit())
+            })
+        };
+
+        for pkg in doc["packages"].as_array().unwrap() {
+            let id = pkg["SPDXID"].as_str().unwrap();
+            let contained: Vec<_> = doc["relationships"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|rel| rel["spdxElementId"] == id && rel["relationshipType"] == "CONTAINS")
+                .map(|rel| rel["relatedSpdxElement"].as_str().unwrap())
+                .collect();
+
+            if pkg["filesAnalyzed"].as_bool().unwrap() {
+                assert!(is_hex(
+                    &pkg["packageVerificationCode"]["packageVerificationCodeValue"],
+                    40
+                ));
+                assert!(!pkg["licenseInfoFromFiles"].as_array().unwrap().is_empty());
+
+                for file in doc["files"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .filter(|file| contained.contains(&file["SPDXID"].as_str().unwrap()))
+                {
+                    assert!(file["checksums"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .any(|cs| cs["algorithm"] == "SHA1" && is_hex(&cs["checksumValue"], 40)));
+                }
+            } else {
+                // A package whose files were not analyzed can't contain any
+                assert!(contained.is_empty(), "{} contains files", id);
+                assert!(pkg.get("hasFiles").is_none());
+                assert!(pkg.get("packageVerificationCode").is_none());
+                assert!(pkg.get("licenseInfoFromFiles").is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn generates_valid_document() {
+        let ws = Workspace::new("spdx");
+        let app = ws
+            .krates
+            .krates()
+            .find(|kn| kn.krate.name == "app")
+            .unwrap();
+        let dep = ws
+            .krates
+            .krates()
+            .find(|kn| kn.krate.name == "dep")
+            .unwrap();
+
+        let krate_license = |krate, license_files| licenses::KrateLicense {
+            krate,
+            lic_info: LicenseInfo::Expr(spdx::Expression::parse("MIT").unwrap()),
+            provenance: licenses::Provenance::FileSystem,
+            license_files,
+            notices: Vec::new(),
+            copyrights: Vec::new(),
+        };
+
+        let nfos = vec![
+            krate_license(
+                &app.krate,
+                vec![license_file(ws.root.join("app/LICENSE"), "app license")],
+            ),
+            krate_license(
+                &dep.krate,
+                vec![
+                    license_file(ws.root.join("dep/LICENSE"), "dep license"),
+                    // Files retrieved from git repositories aren't in the package
+                    license_file("COPYING".into(), "git license"),
+                ],
+            ),
+        ];
+        let resolved = vec![None, None];
+        let paths = cargo_about::DependencyPaths::new(&ws.krates);
+
+        let doc: serde_json::Value =
+            serde_json::from_str(&render_json(&ws.krates, &paths, &nfos, &resolved).unwrap())
+                .unwrap();
+        validate(&doc);
+
+        let packages = doc["packages"].as_array().unwrap();
+        assert_eq!(packages[0]["name"], "app");
+        assert_eq!(packages[0]["filesAnalyzed"], false);
+        assert_eq!(packages[1]["name"], "dep");
+        assert_eq!(packages[1]["filesAnalyzed"], true);
+
+        // The verification code covers every file in the package, except the
+        // file cargo uses to mark it as unpacked
+        let mut sha1s = [
+            sha1("[package]\nname = \"dep\"\n"),
+            sha1("dep license"),
+            sha1(""),
+        ];
+        sha1s.sort();
+        assert_eq!(
+            packages[1]["packageVerificationCode"]["packageVerificationCodeValue"],
+            sha1(&sha1s.concat())
+        );
+
+        let files = doc["files"].as_array().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0]["fileName"], "./LICENSE");
+        assert_eq!(
+            files[0]["checksums"][0]["checksumValue"],
+            sha1("dep license")
+        );
+        assert_eq!(packages[1]["hasFiles"][0], files[0]["SPDXID"]);
+
+        // In the tag-value serialization files belong to the package they follow
+        let tag_value = render_tag_value(&ws.krates, &paths, &nfos, &resolved).unwrap();
+        let dep_pkg = tag_value.find("PackageName: dep").unwrap();
+        let file = tag_value.find("FileName: ./LICENSE").unwrap();
+        assert!(tag_value.find("PackageName: app").unwrap() < dep_pkg);
+        assert!(dep_pkg < file);
+        assert!(tag_value.contains("FilesAnalyzed: false"));
+        assert!(tag_value.contains(&format!(
+            "PackageVerificationCode: {}",
+            sha1(&sha1s.concat())
+        )));
+    }
+}
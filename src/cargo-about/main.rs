@@ -11,7 +11,7 @@
     clippy::dbg_macro,
     clippy::debug_assert_with_mut_call,
     clippy::doc_markdown,
-    clippy::empty_enum,
+    clippy::empty_enums,
     clippy::enum_glob_use,
     clippy::exit,
     clippy::expl_impl_clone_on_copy,
@@ -39,12 +39,10 @@
     clippy::map_err_ignore,
     clippy::map_flatten,
     clippy::map_unwrap_or,
-    clippy::match_on_vec_items,
     clippy::match_same_arms,
     clippy::match_wild_err_arm,
     clippy::match_wildcard_for_single_variants,
     clippy::mem_forget,
-    clippy::mismatched_target_os,
     clippy::missing_enforced_import_renames,
     clippy::mut_mut,
     clippy::mutex_integer,
@@ -63,7 +61,6 @@
     clippy::string_add_assign,
     clippy::string_add,
     clippy::string_lit_as_bytes,
-    clippy::string_to_string,
     clippy::todo,
     clippy::trait_duplication_in_bounds,
     clippy::unimplemented,
@@ -81,7 +78,11 @@
 
 use anyhow::Context as _;
 
+mod cache;
+mod check;
 mod clarify;
+mod common;
+mod explain;
 mod generate;
 mod init;
 
@@ -96,6 +97,13 @@ enum Command {
     Init(init::Args),
     /// Computes a clarification for a file
     Clarify(clarify::Args),
+    /// Manages the persistent cache of remotely retrieved data
+    Cache(cache::Args),
+    /// Checks that the license requirements of every crate are satisfied,
+    /// without generating any output
+    Check(check::Args),
+    /// Explains how the license of a crate was determined
+    Explain(explain::Args),
 }
 
 #[derive(clap::ArgEnum, Copy, Clone, Debug)]
@@ -177,7 +185,8 @@ fn setup_logger(level: log::LevelFilter) -> Result<(), fern::InitError> {
     Ok(())
 }
 
-fn real_main() -> anyhow::Result<()> {
+/// Runs the specified subcommand, returning the exit code
+fn real_main() -> anyhow::Result<i32> {
     use clap::Parser;
 
     let args = Opts::parse_from({
@@ -193,15 +202,21 @@ fn real_main() -> anyhow::Result<()> {
     setup_logger(args.log_level)?;
 
     match args.cmd {
-        Command::Generate(gen) => generate::cmd(gen, args.color),
-        Command::Init(init) => init::cmd(init),
-        Command::Clarify(clarify) => clarify::cmd(clarify),
+        Command::Generate(gen) => generate::cmd(gen, args.color)?,
+        Command::Init(init) => init::cmd(init)?,
+        Command::Clarify(clarify) => clarify::cmd(clarify)?,
+        Command::Cache(cache) => cache::cmd(cache)?,
+        Command::Check(check) => return check::cmd(check, args.color),
+        Command::Explain(explain) => explain::cmd(explain, args.color)?,
     }
+
+    Ok(0)
 }
 
 fn main() {
     match real_main() {
-        Ok(_) => {}
+        Ok(0) => {}
+        Ok(code) => std::process::exit(code),
         Err(e) => {
             log::error!("{:#}", e);
             std::process::exit(1);
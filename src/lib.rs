@@ -11,7 +11,7 @@
     clippy::dbg_macro,
     clippy::debug_assert_with_mut_call,
     clippy::doc_markdown,
-    clippy::empty_enum,
+    clippy::empty_enums,
     clippy::enum_glob_use,
     clippy::exit,
     clippy::expl_impl_clone_on_copy,
@@ -39,12 +39,10 @@
     clippy::map_err_ignore,
     clippy::map_flatten,
     clippy::map_unwrap_or,
-    clippy::match_on_vec_items,
     clippy::match_same_arms,
     clippy::match_wild_err_arm,
     clippy::match_wildcard_for_single_variants,
     clippy::mem_forget,
-    clippy::mismatched_target_os,
     clippy::missing_enforced_import_renames,
     clippy::mut_mut,
     clippy::mutex_integer,
@@ -63,7 +61,6 @@
     clippy::string_add_assign,
     clippy::string_add,
     clippy::string_lit_as_bytes,
-    clippy::string_to_string,
     clippy::todo,
     clippy::trait_duplication_in_bounds,
     clippy::unimplemented,
@@ -182,11 +179,20 @@ pub fn get_all_crates(
     all_features: bool,
     features: Vec<String>,
     workspace: bool,
+    network: licenses::fetch::NetworkMode,
     cfg: &licenses::config::Config,
 ) -> anyhow::Result<Krates> {
     let mut mdc = krates::Cmd::new();
     mdc.manifest_path(cargo_toml);
 
+    // Ensure that cargo itself also doesn't access the network if we aren't
+    // allowed to
+    mdc.lock_opts(krates::LockOptions {
+        frozen: network == licenses::fetch::NetworkMode::Frozen,
+        locked: network == licenses::fetch::NetworkMode::Frozen,
+        offline: network != licenses::fetch::NetworkMode::Online,
+    });
+
     // The metadata command builder is weird and only allows you to specify
     // one of these, but really you might need to do multiple of them
     if no_default_features {
@@ -235,6 +241,102 @@ pub fn get_all_crates(
     Ok(graph)
 }
 
+/// The shortest dependency path from each root, by default the workspace
+/// members, to the crates in the graph, which explains why a crate is in the
+/// graph
+pub struct DependencyPaths<'k> {
+    krates: &'k Krates,
+    /// For each root, the crate that each crate was first reached from in a
+    /// breadth first search from the root. The root is its own parent, and
+    /// crates that aren't reachable from the root have none.
+    parents: Vec<(krates::NodeId, Vec<Option<krates::NodeId>>)>,
+}
+
+impl<'k> DependencyPaths<'k> {
+    /// Finds the dependency paths from each of the workspace members
+    pub fn new(krates: &'k Krates) -> Self {
+        Self::from_roots(krates, krates.workspace_members().map(|member| &member.id))
+    }
+
+    /// Finds the dependency paths from each of the specified crates, roots
+    /// that aren't in the graph are ignored
+    pub fn from_roots<'r>(
+        krates: &'k Krates,
+        roots: impl IntoIterator<Item = &'r krates::Kid>,
+    ) -> Self {
+        let graph = krates.graph();
+
+        let parents = roots
+            .into_iter()
+            .filter_map(|kid| krates.nid_for_kid(kid))
+            .map(|root| {
+                let mut parents = vec![None; graph.node_count()];
+                parents[root.index()] = Some(root);
+
+                let mut queue = std::collections::VecDeque::new();
+                queue.push_back(root);
+
+                while let Some(nid) = queue.pop_front() {
+                    for dep in graph.neighbors_directed(nid, krates::petgraph::Direction::Outgoing)
+                    {
+                        if parents[dep.index()].is_none() {
+                            parents[dep.index()] = Some(nid);
+                            queue.push_back(dep);
+                        }
+                    }
+                }
+
+                (root, parents)
+            })
+            .collect();
+
+        Self { krates, parents }
+    }
+
+    /// The crates the paths start from
+    pub fn roots(&self) -> impl Iterator<Item = &'k Krate> + '_ {
+        let graph = self.krates.graph();
+        self.parents
+            .iter()
+            .map(move |(root, _)| &graph[*root].krate)
+    }
+
+    /// Whether the crate can be reached from any of the roots
+    pub fn contains(&self, kid: &krates::Kid) -> bool {
+        self.krates.nid_for_kid(kid).map_or(false, |nid| {
+            self.parents
+                .iter()
+                .any(|(_, parents)| parents[nid.index()].is_some())
+        })
+    }
+
+    /// Gets the shortest path from each root that depends on the crate,
+    /// starting with the root and ending with the crate
+    pub fn paths(&self, kid: &krates::Kid) -> Vec<Vec<&'k Krate>> {
+        let graph = self.krates.graph();
+        let nid = match self.krates.nid_for_kid(kid) {
+            Some(nid) => nid,
+            None => return Vec::new(),
+        };
+
+        self.parents
+            .iter()
+            .filter_map(|(root, parents)| {
+                let mut path = vec![&graph[nid].krate];
+                let mut current = nid;
+
+                while current != *root {
+                    current = parents[current.index()]?;
+                    path.push(&graph[current].krate);
+                }
+
+                path.reverse();
+                Some(path)
+            })
+            .collect()
+    }
+}
+
 #[inline]
 pub fn to_hex(bytes: &[u8]) -> String {
     let mut s = String::with_capacity(bytes.len() * 2);
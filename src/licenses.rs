@@ -1,4 +1,6 @@
+pub mod cache;
 pub mod config;
+pub mod copyright;
 pub mod fetch;
 pub mod resolution;
 mod scan;
@@ -9,7 +11,7 @@ use anyhow::Context as _;
 use krates::Utf8PathBuf as PathBuf;
 use rayon::prelude::*;
 pub use resolution::Resolved;
-use std::{cmp, fmt, sync::Arc};
+use std::{cmp, fmt, hash::Hasher, sync::Arc};
 
 const LICENSE_CACHE: &[u8] = include_bytes!("../spdx_cache.bin.zstd");
 
@@ -20,7 +22,7 @@ pub fn store_from_cache() -> anyhow::Result<LicenseStore> {
     askalono::Store::from_cache(LICENSE_CACHE).context("failed to load license store")
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 #[allow(clippy::large_enum_variant)]
 pub enum LicenseInfo {
     Expr(spdx::Expression),
@@ -39,6 +41,7 @@ impl fmt::Display for LicenseInfo {
 }
 
 /// The contents of a file with license info in it
+#[derive(Clone)]
 pub enum LicenseFileKind {
     /// The license file is the canonical text of the license
     Text(String),
@@ -50,16 +53,24 @@ pub enum LicenseFileKind {
     Header,
 }
 
+#[derive(Clone)]
 pub struct LicenseFile {
     /// The SPDX requirement expression detected for the file
     pub license_expr: spdx::Expression,
-    /// Full path of the file which had license data in it
+    /// Full path of the file which had license data in it. Files that were
+    /// retrieved from a git repository only exist in memory, and their path
+    /// is relative to the root of the repository instead
     pub path: PathBuf,
     /// The confidence score for the license, the closer to the canonical
     /// license text it is, the closer it approaches 1.0
     pub confidence: f32,
     /// The contents of the file
     pub kind: LicenseFileKind,
+    /// The byte range of the license text, if it is only a part of the file,
+    /// eg. if the file contains multiple licenses
+    pub range: Option<std::ops::Range<usize>>,
+    /// Where the license information for the file was determined
+    pub provenance: Provenance,
 }
 
 impl Ord for LicenseFile {
@@ -91,10 +102,76 @@ impl PartialEq for LicenseFile {
 
 impl Eq for LicenseFile {}
 
+/// A NOTICE file, which Apache-2.0 section 4(d) requires to be included
+/// with any redistribution of the work
+#[derive(Clone)]
+pub struct Notice {
+    /// The path of the file, relative to the crate root, or to the repository
+    /// root if it was retrieved from the crate's source repository
+    pub path: PathBuf,
+    /// The contents of the file
+    pub text: String,
+}
+
+/// Where license information was determined, for a crate or a single file
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Provenance {
+    /// The crate is private, and private crates are ignored
+    Private,
+    /// The crate wasn't selected, so its license information wasn't gathered
+    Unselected,
+    /// A workaround built-in to cargo-about
+    Workaround {
+        /// The name of the workaround
+        name: String,
+    },
+    /// A clarification in the user's config
+    Clarification,
+    /// A definition harvested by clearlydefined.io
+    ClearlyDefined {
+        /// The harvest tools, and their versions, that harvested the definition
+        harvest: Vec<String>,
+        /// The effective score of the definition, 0-100
+        score: u8,
+    },
+    /// The crate's source files on disk were scanned
+    FileSystem,
+    /// A license file in the crate's `additional` config
+    Additional,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Private => f.write_str("private"),
+            Self::Unselected => f.write_str("not selected"),
+            Self::Workaround { name } => write!(f, "workaround '{}'", name),
+            Self::Clarification => f.write_str("clarification"),
+            Self::ClearlyDefined { harvest, score } => {
+                write!(f, "clearlydefined.io (score {}", score)?;
+                if !harvest.is_empty() {
+                    write!(f, ", harvested by {}", harvest.join(", "))?;
+                }
+                f.write_str(")")
+            }
+            Self::FileSystem => f.write_str("file system scan"),
+            Self::Additional => f.write_str("additional license file"),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct KrateLicense<'krate> {
     pub krate: &'krate Krate,
     pub lic_info: LicenseInfo,
+    /// Where the license information was determined
+    pub provenance: Provenance,
     pub license_files: Vec<LicenseFile>,
+    /// The NOTICE files for the crate
+    pub notices: Vec<Notice>,
+    /// The copyright statements in the crate's license files
+    pub copyrights: Vec<copyright::Copyright>,
 }
 
 impl<'krate> Ord for KrateLicense<'krate> {
@@ -120,10 +197,72 @@ impl<'krate> PartialEq for KrateLicense<'krate> {
 
 impl<'krate> Eq for KrateLicense<'krate> {}
 
+impl<'krate> KrateLicense<'krate> {
+    /// Extracts the copyright statements that apply to one of the crate's
+    /// license files.
+    ///
+    /// The copyright of a license usually precedes its text, which is
+    /// outside of the range of a license file that was split from a file that
+    /// contains multiple licenses, so for those the copyrights are extracted
+    /// from the part of the file between the previous license and the end of
+    /// this one.
+    pub fn license_file_copyrights(&self, lf: &LicenseFile) -> Vec<copyright::Copyright> {
+        let text = match &lf.kind {
+            LicenseFileKind::Text(text) => text,
+            // The lines of additional license files are chosen by the user,
+            // so they can include the copyright if it is needed
+            LicenseFileKind::AddendumText(text, _) => return copyright::extract(text),
+            LicenseFileKind::Header => return Vec::new(),
+        };
+
+        let range = match &lf.range {
+            Some(range) => range,
+            None => return copyright::extract(text),
+        };
+
+        // Files retrieved from a git repository aren't on disk, and a file
+        // could have changed since it was cached, so only use the contents
+        // if they still match the license text
+        let path = self.krate.manifest_path.parent().unwrap().join(&lf.path);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) if contents.get(range.clone()) == Some(text.as_str()) => contents,
+            _ => return copyright::extract(text),
+        };
+
+        let others: Vec<_> = self
+            .license_files
+            .iter()
+            .filter(|other| other.path == lf.path)
+            .filter_map(|other| other.range.clone())
+            .filter(|other| *other != *range)
+            .collect();
+
+        let start = others
+            .iter()
+            .filter(|other| other.start < range.start)
+            .map(|other| copyright_end(&contents, other.clone()))
+            .max()
+            .unwrap_or(0);
+
+        // Copyrights after the last license in the file most likely belong
+        // to it as well
+        let end = if others.iter().any(|other| other.start > range.start) {
+            copyright_end(&contents, range.clone())
+        } else {
+            contents.len()
+        };
+
+        copyright::extract(&contents[start.min(range.start)..end.max(range.start)])
+    }
+}
+
 pub struct Gatherer {
     store: Arc<LicenseStore>,
     cd_client: cd::client::Client,
     threshold: f32,
+    cache: Option<cache::Cache>,
+    network: fetch::Network,
+    selection: Option<Vec<krates::Kid>>,
 }
 
 impl Gatherer {
@@ -132,17 +271,35 @@ impl Gatherer {
             store,
             threshold: 0.8,
             cd_client: client,
+            cache: None,
+            network: fetch::Network::default(),
+            selection: None,
         }
     }
 
+    /// Only gathers license information for the specified crates, rather
+    /// than every crate in the graph
+    pub fn with_selection(mut self, selection: Vec<krates::Kid>) -> Self {
+        self.selection = Some(selection);
+        self
+    }
+
+    /// Uses the specified persistent cache to avoid retrieving the same data
+    /// from remote sources on every run
+    pub fn with_cache(mut self, cache: Option<cache::Cache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Sets the gate all network access is routed through. If the network
+    /// is not online, remote data will only be served from the cache.
+    pub fn with_network(mut self, network: fetch::Network) -> Self {
+        self.network = network;
+        self
+    }
+
     pub fn with_confidence_threshold(mut self, threshold: f32) -> Self {
-        self.threshold = if threshold > 1.0 {
-            1.0
-        } else if threshold < 0.0 {
-            0.0
-        } else {
-            threshold
-        };
+        self.threshold = threshold.clamp(0.0, 1.0);
         self
     }
 
@@ -153,20 +310,11 @@ impl Gatherer {
     ) -> Vec<KrateLicense<'krate>> {
         let mut licensed_krates = Vec::with_capacity(krates.len());
 
-        let threshold = self.threshold;
-        let min_threshold = threshold - 0.5;
+        let (strategy, segment_strategy) = self.strategies();
 
-        let strategy = askalono::ScanStrategy::new(&self.store)
-            .mode(askalono::ScanMode::Elimination)
-            .confidence_threshold(if min_threshold < 0.1 {
-                0.1
-            } else {
-                min_threshold
-            })
-            .optimize(false)
-            .max_passes(1);
-
-        let git_cache = fetch::GitCache::default();
+        let git_cache = fetch::GitCache::default()
+            .with_cache(self.cache.clone())
+            .with_network(self.network.clone());
 
         // If we're ignoring crates that are private, just add them
         // to the list so all of the following gathers ignore them
@@ -182,7 +330,10 @@ impl Gatherer {
                         licensed_krates.push(KrateLicense {
                             krate: &krate.krate,
                             lic_info: LicenseInfo::Ignore,
+                            provenance: Provenance::Private,
                             license_files: Vec::new(),
+                            notices: Vec::new(),
+                            copyrights: Vec::new(),
                         });
                     }
                 }
@@ -191,6 +342,30 @@ impl Gatherer {
             licensed_krates.sort();
         }
 
+        // Crates that aren't selected are treated as already gathered, so
+        // none of the following gathers do any work for them
+        if let Some(selection) = &self.selection {
+            for krate in krates.krates().map(|kn| &kn.krate) {
+                if selection.contains(&krate.id) {
+                    continue;
+                }
+
+                if let Err(i) = binary_search(&licensed_krates, krate) {
+                    licensed_krates.insert(
+                        i,
+                        KrateLicense {
+                            krate,
+                            lic_info: LicenseInfo::Ignore,
+                            provenance: Provenance::Unselected,
+                            license_files: Vec::new(),
+                            notices: Vec::new(),
+                            copyrights: Vec::new(),
+                        },
+                    );
+                }
+            }
+        }
+
         // Workarounds are built-in to cargo-about to deal with issues that certain
         // common crates have
         workarounds::apply_workarounds(krates, cfg, &git_cache, &mut licensed_krates);
@@ -206,12 +381,113 @@ impl Gatherer {
         self.gather_clearly_defined(krates, cfg, &strategy, &mut licensed_krates);
 
         // Finally, crawl the crate sources on disk to try and determine licenses
-        self.gather_file_system(krates, &strategy, &mut licensed_krates);
+        self.gather_file_system(
+            krates,
+            cfg,
+            &strategy,
+            &segment_strategy,
+            &mut licensed_krates,
+        );
 
         licensed_krates.sort();
+
+        if let Some(selection) = &self.selection {
+            licensed_krates.retain(|kl| selection.contains(&kl.krate.id));
+        }
+
+        // Apache-2.0 requires that NOTICE files are redistributed along with
+        // the license, so they are gathered regardless of how the license was
+        self.gather_notices(cfg, &git_cache, &mut licensed_krates);
+
+        gather_copyrights(cfg, &mut licensed_krates);
+
         licensed_krates
     }
 
+    fn gather_notices(
+        &self,
+        cfg: &config::Config,
+        gc: &fetch::GitCache,
+        licensed_krates: &mut [KrateLicense<'_>],
+    ) {
+        // Crates aren't required to have a NOTICE, so when we can't go online
+        // we only use ones that were already cached, without reporting the
+        // ones that weren't as denied fetches
+        let offline_gc;
+        let gc = if self.network.is_online() {
+            gc
+        } else {
+            offline_gc = gc
+                .clone()
+                .with_network(fetch::Network::new(fetch::NetworkMode::Offline));
+            &offline_gc
+        };
+
+        licensed_krates.par_iter_mut().for_each(|kl| {
+            if matches!(kl.lic_info, LicenseInfo::Ignore) {
+                return;
+            }
+
+            kl.notices = scan::find_notices(kl.krate.manifest_path.parent().unwrap());
+
+            if !kl.notices.is_empty() {
+                return;
+            }
+
+            // Clarifications retrieve files from the source repository when
+            // they weren't packaged, in which case the NOTICE is likely to be
+            // missing from the package as well
+            let clarification = match cfg
+                .krate_configs(kl.krate)
+                .filter_map(|kc| kc.clarify.as_ref())
+                .find(|clarification| !clarification.git.is_empty())
+            {
+                Some(clarification) => clarification,
+                None => return,
+            };
+
+            // If the clarification already retrieved a NOTICE, its contents
+            // were validated against the configured checksum, so reuse them
+            // instead of retrieving the file again
+            if let Some(git_notice) = clarification
+                .git
+                .iter()
+                .find(|cf| cf.path.file_name().map_or(false, scan::is_notice))
+            {
+                if let Some(lf) = kl
+                    .license_files
+                    .iter()
+                    .find(|lf| lf.path == git_notice.path && lf.range.is_none())
+                {
+                    if let LicenseFileKind::Text(text) = &lf.kind {
+                        kl.notices.push(Notice {
+                            path: lf.path.clone(),
+                            text: text.clone(),
+                        });
+                    }
+                }
+
+                return;
+            }
+
+            let path = krates::Utf8Path::new("NOTICE");
+
+            match gc.retrieve(kl.krate, path, &clarification.override_git_commit) {
+                Ok(text) => kl.notices.push(Notice {
+                    path: path.to_owned(),
+                    text: text.as_ref().clone(),
+                }),
+                Err(e) => {
+                    log::debug!(
+                        "unable to retrieve NOTICE for crate '{}': {:#}",
+                        kl.krate,
+                        e
+                    );
+                }
+            }
+        });
+    }
+
     #[allow(clippy::unused_self)]
     fn gather_clarified<'k>(
         &self,
@@ -220,14 +496,21 @@ impl Gatherer {
         gc: &fetch::GitCache,
         licensed_krates: &mut Vec<KrateLicense<'k>>,
     ) {
-        for (krate, clarification) in krates.krates().filter_map(|kn| {
-            cfg.crates
-                .get(&kn.krate.name)
-                .and_then(|kc| kc.clarify.as_ref())
-                .map(|cl| (&kn.krate, cl))
-        }) {
-            if let Err(i) = binary_search(licensed_krates, krate) {
-                match apply_clarification(gc, krate, clarification) {
+        for krate in krates.krates().map(|kn| &kn.krate) {
+            let i = match binary_search(licensed_krates, krate) {
+                Ok(_) => continue,
+                Err(i) => i,
+            };
+
+            // Multiple clarifications can apply to the same crate, eg. if they
+            // are scoped to overlapping version ranges, the first one that can
+            // be validated is used
+            let mut failures = Vec::new();
+            for clarification in cfg
+                .krate_configs(krate)
+                .filter_map(|kc| kc.clarify.as_ref())
+            {
+                match apply_clarification(gc, krate, clarification, &Provenance::Clarification) {
                     Ok(lic_files) => {
                         log::debug!(
                             "applying clarification expression '{}' to crate {}",
@@ -239,15 +522,26 @@ impl Gatherer {
                             KrateLicense {
                                 krate,
                                 lic_info: LicenseInfo::Expr(clarification.license.clone()),
+                                provenance: Provenance::Clarification,
                                 license_files: lic_files,
+                                notices: Vec::new(),
+                                copyrights: Vec::new(),
                             },
                         );
+                        failures.clear();
+                        break;
                     }
-                    Err(e) => {
-                        log::warn!("failed to validate all files specified in clarification for crate {}: {}", krate, e);
-                    }
+                    Err(e) => failures.push(e),
                 }
             }
+
+            for e in failures {
+                log::warn!(
+                    "failed to validate all files specified in clarification for crate {}: {}",
+                    krate,
+                    e
+                );
+            }
         }
     }
 
@@ -262,168 +556,241 @@ impl Gatherer {
             return;
         }
 
-        let reqs = cd::definitions::get(
-            10,
-            krates.krates().filter_map(|krate| {
-                if binary_search(licensed_krates, &krate.krate).is_ok() {
-                    return None;
-                }
+        let mut definitions = Vec::new();
+        let mut requested = Vec::new();
 
-                // Ignore local and git sources in favor of scanning those on the local disk
-                if krate
-                    .krate
-                    .source
-                    .as_ref()
-                    .map_or(false, |src| src.is_crates_io())
-                {
-                    Some(cd::Coordinate {
-                        shape: cd::Shape::Crate,
-                        provider: cd::Provider::CratesIo,
-                        // Rust crates, at least on crates.io, don't have a namespace
-                        namespace: None,
-                        name: krate.krate.name.clone(),
-                        version: cd::CoordVersion::Semver(krate.krate.version.clone()),
-                        // TODO: maybe set this if it's overriden in the config? seems messy though
-                        curation_pr: None,
-                    })
+        for krate in krates.krates().map(|kn| &kn.krate) {
+            if binary_search(licensed_krates, krate).is_ok() {
+                continue;
+            }
+
+            // Ignore local and git sources in favor of scanning those on the local disk
+            if !krate
+                .source
+                .as_ref()
+                .map_or(false, |src| src.is_crates_io())
+            {
+                continue;
+            }
+
+            if let Some(def) = self.cache.as_ref().and_then(|cache| {
+                cache.clearly_defined(&krate.name, &krate.version, !self.network.is_online())
+            }) {
+                if def.unharvested {
+                    log::debug!("'{}' has not been harvested by clearlydefined.io", krate);
                 } else {
-                    None
+                    log::debug!("using cached definition for '{}'", krate);
+                    definitions.push(def);
                 }
-            }),
-        );
+                continue;
+            }
 
-        //let threshold = std::cmp::min(std::cmp::max(10, (self.threshold * 100.0) as u8), 100);
-        let collected: Vec<_> = reqs.par_bridge().filter_map(|req| {
-            match self.cd_client.execute::<cd::definitions::GetResponse>(req) {
-                Ok(response) => {
-                    Some(response.definitions.into_iter().filter_map(|def| {
-                        if def.described.is_none() {
-                            log::warn!("the definition for {} has not been harvested", def.coordinates);
-                            return None;
+            if self
+                .network
+                .allow(krate, "definition from clearlydefined.io")
+                .is_err()
+            {
+                continue;
+            }
+
+            requested.push(krate);
+        }
+
+        const CHUNK_SIZE: usize = 10;
+
+        let retrieved: Vec<_> = requested
+            .par_chunks(CHUNK_SIZE)
+            .filter_map(|chunk| {
+                let coordinates = chunk.iter().map(|krate| cd::Coordinate {
+                    shape: cd::Shape::Crate,
+                    provider: cd::Provider::CratesIo,
+                    // Rust crates, at least on crates.io, don't have a namespace
+                    namespace: None,
+                    name: krate.name.clone(),
+                    version: cd::CoordVersion::Semver(krate.version.clone()),
+                    // TODO: maybe set this if it's overriden in the config? seems messy though
+                    curation_pr: None,
+                });
+                let req = cd::definitions::get(CHUNK_SIZE, coordinates).next()?;
+
+                match self.cd_client.execute::<cache::GetResponse>(req) {
+                    Ok(mut response) => {
+                        // Crates clearlydefined.io doesn't know about at all are
+                        // treated the same as ones it hasn't harvested
+                        for krate in chunk {
+                            let version = krate.version.to_string();
+                            if !response
+                                .definitions
+                                .iter()
+                                .any(|def| def.name == krate.name && def.version == version)
+                            {
+                                response
+                                    .definitions
+                                    .push(cache::Definition::unharvested(&krate.name, &version));
+                            }
                         }
 
-                        // Since we only ever retrieve license information for crates on crates.io
-                        // they _should_ always have a valid semver
-                        let version = match &def.coordinates.revision {
-                            cd::CoordVersion::Semver(vers) => vers.clone(),
-                            cd::CoordVersion::Any(vers) => {
-                                log::warn!(
-                                    "the definition for {} does not have a valid semver '{}'",
-                                    def.coordinates,
-                                    vers,
-                                );
-                                return None;
+                        if let Some(cache) = &self.cache {
+                            for def in &response.definitions {
+                                cache.store_clearly_defined(def);
                             }
-                        };
-
-                        match krates.krates_by_name(&def.coordinates.name).find_map(|(_, kn)| {
-                            if kn.krate.version == version {
-                                Some(&kn.krate)
-                            } else {
-                                None
-                            }})
-                        {
-                            Some(krate) => {
-                                let info = krate.get_license_expression();
-
-                                // clearly defined doesn't provide per-file scores, so we just use
-                                // the overall score for the entire crate
-                                let confidence = def.scores.effective as f32 / 100.0;
-
-                                let license_files = def.files.into_iter().filter_map(|cd_file| {
-                                    // Retrieve (and validate) the text of the file if clearlydefined thinks it is a license file
-                                    let license_text = if cd_file.natures.iter().any(|s| s == "license") {
-                                        let root_path = krate.manifest_path.parent().unwrap();
-                                        let path = root_path.join(&cd_file.path);
-                                        match std::fs::read_to_string(&path) {
-                                            Ok(text) => {
-                                                if let Some(expected) = cd_file.hashes.as_ref().and_then(|hashes| hashes.sha256.as_ref()) {
-                                                    if let Err(err) = crate::validate_sha256(&text, expected) {
-                                                        log::warn!("file '{}' for crate '{}' marked as a license but the sha256 hash could not be verified: {}", path, krate, err);
-                                                        return None;
-                                                    }
-                                                }
-
-                                                Some(text)
-                                            }
-                                            Err(err) => {
-                                                log::warn!("failed to read license from '{}' for crate '{}': {}", path, krate, err);
-                                                return None;
-                                            }
-                                        }
-                                    } else {
-                                        None
-                                    };
-
-                                    let path = cd_file.path;
-
-                                    // clearly defined will attach a license identifier to any file
-                                    // with a license or SPDX identifier, but like askalono it won't
-                                    // detect all licenses if there are multiple in a single file
-                                    match (cd_file.license, license_text) {
-                                        (Some(lic), license_text) => {
-                                            let license_expr = match spdx::Expression::parse_mode(&lic, spdx::ParseMode::LAX) {
-                                                Ok(expr) => expr,
-                                                Err(err) => {
-                                                    log::warn!("clearlydefined detected license '{}' in '{}' for crate '{}', but it can't be parsed: {}", lic, path, krate, err);
-                                                    return None;
-                                                }
-                                            };
-
-                                            Some(LicenseFile {
-                                                license_expr,
-                                                path,
-                                                confidence,
-                                                kind: license_text.map_or(LicenseFileKind::Header, LicenseFileKind::Text),
-                                            })
-                                        }
-                                        (None, Some(license_text)) => {
-                                            // For some reason, clearlydefined will correctly identify text as being a
-                                            // license but won't give it an expression, so we have to figure out what it
-                                            // is, but at least have high confidence that it will result in a match
-                                            scan::check_is_license_file(path.clone(), license_text, strategy, self.threshold)
-                                                .or_else(|| {
-                                                    log::warn!("clearlydefined detected license in '{}' for crate '{}', but it we failed to determine what its license was", path, krate);
-                                                    None
-                                                })
-                                        }
-                                        _ => None,
-                                    }
-                                }).collect();
-
-                                Some(KrateLicense {
-                                    krate,
-                                    lic_info: info,
-                                    license_files,
-                                })
+                        }
+
+                        Some(
+                            response
+                                .definitions
+                                .into_iter()
+                                .filter(|def| !def.unharvested)
+                                .collect::<Vec<_>>(),
+                        )
+                    }
+                    Err(err) => {
+                        log::warn!(
+                            "failed to request license information from clearly defined: {:#}",
+                            err
+                        );
+                        None
+                    }
+                }
+            })
+            .flatten()
+            .collect();
+
+        definitions.extend(retrieved);
+
+        let mut collected: Vec<_> = definitions
+            .into_par_iter()
+            .filter_map(|def| self.license_from_definition(krates, cfg, def, strategy))
+            .collect();
+
+        licensed_krates.append(&mut collected);
+        licensed_krates.sort();
+    }
+
+    fn license_from_definition<'k>(
+        &self,
+        krates: &'k Krates,
+        cfg: &config::Config,
+        def: cache::Definition,
+        strategy: &askalono::ScanStrategy<'_>,
+    ) -> Option<KrateLicense<'k>> {
+        let krate = krates
+            .krates_by_name(&def.name)
+            .find_map(|(_, kn)| (kn.krate.version.to_string() == def.version).then(|| &kn.krate))?;
+
+        let info = krate.get_license_expression();
+
+        // clearly defined doesn't provide per-file scores, so we just use
+        // the overall score for the entire crate
+        let confidence = def.score as f32 / 100.0;
+
+        let provenance = Provenance::ClearlyDefined {
+            harvest: def.harvest.clone(),
+            score: def.score,
+        };
+
+        let mut license_files = def.files.into_iter().filter_map(|cd_file| {
+            // Retrieve (and validate) the text of the file if clearlydefined thinks it is a license file
+            let license_text = if cd_file.is_license {
+                let root_path = krate.manifest_path.parent().unwrap();
+                let path = root_path.join(&cd_file.path);
+                match std::fs::read_to_string(&path) {
+                    Ok(text) => {
+                        if let Some(expected) = &cd_file.sha256 {
+                            if let Err(err) = crate::validate_sha256(&text, expected) {
+                                log::warn!("file '{}' for crate '{}' marked as a license but the sha256 hash could not be verified: {}", path, krate, err);
+                                return None;
                             }
-                            None => None,
                         }
-                    }).collect::<Vec<_>>())
+
+                        Some(text)
+                    }
+                    Err(err) => {
+                        log::warn!("failed to read license from '{}' for crate '{}': {}", path, krate, err);
+                        return None;
+                    }
                 }
-                Err(err) => {
-                    log::warn!(
-                        "failed to request license information from clearly defined: {:#}",
-                        err
-                    );
-                    None
+            } else {
+                None
+            };
+
+            let path = krate.manifest_path.parent().unwrap().join(cd_file.path);
+
+            // clearly defined will attach a license identifier to any file
+            // with a license or SPDX identifier, but like askalono it won't
+            // detect all licenses if there are multiple in a single file
+            match (cd_file.license, license_text) {
+                (Some(lic), license_text) => {
+                    let license_expr = match spdx::Expression::parse_mode(&lic, spdx::ParseMode::LAX) {
+                        Ok(expr) => expr,
+                        Err(err) => {
+                            log::warn!("clearlydefined detected license '{}' in '{}' for crate '{}', but it can't be parsed: {}", lic, path, krate, err);
+                            return None;
+                        }
+                    };
+
+                    Some(LicenseFile {
+                        license_expr,
+                        path,
+                        confidence,
+                        kind: license_text.map_or(LicenseFileKind::Header, LicenseFileKind::Text),
+                        range: None,
+                        provenance: provenance.clone(),
+                    })
+                }
+                (None, Some(license_text)) => {
+                    // For some reason, clearlydefined will correctly identify text as being a
+                    // license but won't give it an expression, so we have to figure out what it
+                    // is, but at least have high confidence that it will result in a match
+                    scan::check_is_license_file(path.clone(), license_text, strategy, self.threshold)
+                        .map(|lf| LicenseFile { provenance: provenance.clone(), ..lf })
+                        .or_else(|| {
+                            log::warn!("clearlydefined detected license in '{}' for crate '{}', but it we failed to determine what its license was", path, krate);
+                            None
+                        })
                 }
+                _ => None,
             }
         }).collect();
 
-        for mut set in collected {
-            licensed_krates.append(&mut set);
-        }
-        licensed_krates.sort();
+        self.apply_krate_files(krate, cfg, strategy, &mut license_files);
+
+        Some(KrateLicense {
+            krate,
+            lic_info: info,
+            provenance,
+            license_files,
+            notices: Vec::new(),
+            copyrights: Vec::new(),
+        })
     }
 
     fn gather_file_system<'k>(
         &self,
         krates: &'k Krates,
+        cfg: &config::Config,
         strategy: &askalono::ScanStrategy<'_>,
+        segment_strategy: &askalono::ScanStrategy<'_>,
         licensed_krates: &mut Vec<KrateLicense<'k>>,
     ) {
-        let threshold = self.threshold;
+        // Registry crates are immutable, so their scans can be cached as long
+        // as nothing that affects the scan changes
+        let checksums = if self.cache.is_some() {
+            cache::registry_checksums(krates.lock_path()).unwrap_or_else(|e| {
+                log::warn!("unable to cache license scans: {:#}", e);
+                Default::default()
+            })
+        } else {
+            Default::default()
+        };
+
+        let scan_params = {
+            let mut hasher = twox_hash::XxHash64::default();
+            hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+            hasher.write(LICENSE_CACHE);
+            hasher.write_u32(self.threshold.to_bits());
+            hasher.finish()
+        };
 
         let mut gathered: Vec<_> = krates
             .krates()
@@ -438,58 +805,356 @@ impl Gatherer {
 
                 let info = krate.get_license_expression();
 
-                let root_path = krate.manifest_path.parent().unwrap();
+                let mut license_files = self.scan_krate(
+                    krate,
+                    cfg,
+                    strategy,
+                    segment_strategy,
+                    cache::checksum(&checksums, krate),
+                    scan_params,
+                );
 
-                let mut license_files = match scan::scan_files(root_path, strategy, threshold) {
-                    Ok(files) => files,
-                    Err(err) => {
-                        log::error!(
-                            "unable to scan for license files for crate '{} - {}': {}",
-                            krate.name,
-                            krate.version,
-                            err
-                        );
-
-                        Vec::new()
-                    }
-                };
-
-                // Condense each license down to the best candidate if
-                // multiple are found
-                license_files.sort();
-
-                let mut expr = None;
-                license_files.retain(|lf| match &expr {
-                    Some(cur) => {
-                        if *cur != lf.license_expr {
-                            expr = Some(lf.license_expr.clone());
-                            true
-                        } else {
-                            false
-                        }
-                    }
-                    None => {
-                        expr = Some(lf.license_expr.clone());
-                        true
-                    }
-                });
+                self.apply_krate_files(krate, cfg, strategy, &mut license_files);
+                condense(&mut license_files);
 
                 Some(KrateLicense {
                     krate,
                     lic_info: info,
+                    provenance: Provenance::FileSystem,
                     license_files,
+                    notices: Vec::new(),
+                    copyrights: Vec::new(),
                 })
             })
             .collect();
 
         licensed_krates.append(&mut gathered);
     }
+
+    /// The strategies used to scan files for licenses, the first for whole
+    /// files, and the second for splitting files that contain multiple licenses
+    fn strategies(&self) -> (askalono::ScanStrategy<'_>, askalono::ScanStrategy<'_>) {
+        let threshold = self.threshold;
+        let min_threshold = threshold - 0.5;
+
+        let strategy = askalono::ScanStrategy::new(&self.store)
+            .mode(askalono::ScanMode::Elimination)
+            .confidence_threshold(if min_threshold < 0.1 {
+                0.1
+            } else {
+                min_threshold
+            })
+            .optimize(false)
+            .max_passes(1);
+
+        // License files that contain multiple licenses are split into each
+        // license, each of which needs to meet the threshold on its own
+        let segment_strategy = askalono::ScanStrategy::new(&self.store)
+            .mode(askalono::ScanMode::TopDown)
+            .confidence_threshold(threshold);
+
+        (strategy, segment_strategy)
+    }
+
+    /// Scans the crate's files for license information, without applying any
+    /// workarounds, clarifications, or crate configuration other than `scan`
+    pub fn scan(&self, krate: &Krate, cfg: &config::Config) -> Vec<LicenseFile> {
+        let (strategy, segment_strategy) = self.strategies();
+        self.scan_krate(krate, cfg, &strategy, &segment_strategy, None, 0)
+    }
+
+    /// Scans the crate's files for license information, reusing the results
+    /// of a previous scan if the crate has a package `checksum` and the scan
+    /// was cached
+    fn scan_krate(
+        &self,
+        krate: &Krate,
+        cfg: &config::Config,
+        strategy: &askalono::ScanStrategy<'_>,
+        segment_strategy: &askalono::ScanStrategy<'_>,
+        checksum: Option<&str>,
+        scan_params: u64,
+    ) -> Vec<LicenseFile> {
+        let globs = cfg.scan_globs(krate);
+        let mode = cfg.scan_mode(krate);
+
+        let cached = self.cache.as_ref().zip(checksum).map(|(cache, checksum)| {
+            let mut hasher = twox_hash::XxHash64::with_seed(scan_params);
+            hasher.write_u8(mode as u8);
+            for glob in &globs {
+                hasher.write(glob.as_bytes());
+            }
+
+            (
+                cache,
+                checksum,
+                cache::scan_key(krate, checksum, hasher.finish()),
+            )
+        });
+
+        if let Some((cache, _, key)) = &cached {
+            if let Some(files) = cache.scan(krate, key) {
+                log::debug!("using cached scan for '{}'", krate);
+                return files;
+            }
+        }
+
+        let root_path = krate.manifest_path.parent().unwrap();
+
+        match scan::scan_files(
+            root_path,
+            &globs,
+            mode,
+            strategy,
+            segment_strategy,
+            self.threshold,
+        ) {
+            Ok(files) => {
+                if let Some((cache, checksum, key)) = &cached {
+                    cache.store_scan(krate, key, checksum, &files);
+                }
+
+                files
+            }
+            Err(err) => {
+                log::error!(
+                    "unable to scan for license files for crate '{} - {}': {}",
+                    krate.name,
+                    krate.version,
+                    err
+                );
+
+                Vec::new()
+            }
+        }
+    }
+
+    /// Applies the `ignore` and `additional` entries of every configuration
+    /// that matches the crate to the license files gathered for it
+    fn apply_krate_files(
+        &self,
+        krate: &Krate,
+        cfg: &config::Config,
+        strategy: &askalono::ScanStrategy<'_>,
+        license_files: &mut Vec<LicenseFile>,
+    ) {
+        let root = krate.manifest_path.parent().unwrap();
+
+        for kc in cfg.krate_configs(krate) {
+            for ignore in &kc.ignore {
+                let count = license_files.len();
+                license_files.retain(|lf| {
+                    lf.path.strip_prefix(root).unwrap_or(&lf.path) != ignore.license_file
+                        || lf.license_expr != ignore.license
+                });
+
+                if license_files.len() == count {
+                    log::warn!(
+                        "ignored license file '{}' ({}) was not detected in crate '{}'",
+                        ignore.license_file,
+                        ignore.license,
+                        krate
+                    );
+                    continue;
+                }
+
+                log::debug!(
+                    "ignoring license file '{}' ({}) in crate '{}'",
+                    ignore.license_file,
+                    ignore.license,
+                    krate
+                );
+
+                // If only part of the file is ignored, the rest of it can
+                // still have license information in it
+                if ignore.is_partial() {
+                    match remove_lines(root, ignore) {
+                        Ok((path, contents)) => {
+                            license_files.extend(scan::check_is_license_file(
+                                path,
+                                contents,
+                                strategy,
+                                self.threshold,
+                            ));
+                        }
+                        Err(err) => {
+                            log::warn!(
+                                "failed to rescan ignored license file '{}' in crate '{}': {:#}",
+                                ignore.license_file,
+                                krate,
+                                err
+                            );
+                        }
+                    }
+                }
+            }
+
+            for additional in &kc.additional {
+                match self.additional_license_file(root, additional, strategy) {
+                    Ok(lf) => license_files.push(lf),
+                    Err(err) => {
+                        log::warn!(
+                            "failed to add additional license file '{}' to crate '{}': {:#}",
+                            additional.license_file,
+                            krate,
+                            err
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn additional_license_file(
+        &self,
+        root: &krates::Utf8Path,
+        additional: &config::Additional,
+        strategy: &askalono::ScanStrategy<'_>,
+    ) -> anyhow::Result<LicenseFile> {
+        let path = root.join(&additional.license_file);
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("unable to read path '{}'", path))?;
+
+        let range = scan::line_range(&contents, additional.license_start, additional.license_end)?;
+        let text = contents[range.clone()].to_owned();
+
+        anyhow::ensure!(!text.trim().is_empty(), "license text is empty");
+
+        // The license is supplied by the user, so it is used regardless of
+        // what is detected, but a mismatch is most likely a mistake
+        match scan::check_is_license_file(path.clone(), text.clone(), strategy, self.threshold) {
+            Some(detected) => {
+                if !detected.license_expr.requirements().all(|dreq| {
+                    additional
+                        .license
+                        .requirements()
+                        .any(|areq| areq.req.license == dreq.req.license)
+                }) {
+                    log::warn!(
+                        "additional license file '{}' is '{}', but '{}' was detected",
+                        path,
+                        additional.license,
+                        detected.license_expr
+                    );
+                }
+            }
+            None => {
+                log::warn!(
+                    "additional license file '{}' is '{}', but no license was detected",
+                    path,
+                    additional.license
+                );
+            }
+        }
+
+        Ok(LicenseFile {
+            license_expr: additional.license.clone(),
+            path,
+            confidence: 1.0,
+            kind: LicenseFileKind::AddendumText(text, root.join(&additional.root)),
+            range: (additional.license_start.is_some() || additional.license_end.is_some())
+                .then(|| range),
+            provenance: Provenance::Additional,
+        })
+    }
+}
+
+/// Condenses the full license texts detected for a crate down to the best
+/// candidate for each license. Additional license files apply to their own
+/// subdirectory of the crate, so they are always kept alongside the crate's
+/// own license file, as are headers, so that every file with a mismatched
+/// license header is reported.
+fn condense(license_files: &mut Vec<LicenseFile>) {
+    license_files.sort();
+
+    let mut expr = None;
+    license_files.retain(|lf| {
+        if !matches!(lf.kind, LicenseFileKind::Text(_)) {
+            return true;
+        }
+
+        match &expr {
+            Some(cur) if *cur == lf.license_expr => false,
+            _ => {
+                expr = Some(lf.license_expr.clone());
+                true
+            }
+        }
+    });
+}
+
+/// Reads an ignored license file, removing the ignored lines from it
+fn remove_lines(
+    root: &krates::Utf8Path,
+    ignore: &config::Ignore,
+) -> anyhow::Result<(PathBuf, String)> {
+    let path = root.join(&ignore.license_file);
+    let mut contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("unable to read path '{}'", path))?;
+
+    let range = scan::line_range(&contents, ignore.license_start, ignore.license_end)?;
+    contents.replace_range(range, "");
+
+    Ok((path, contents))
+}
+
+/// The number of lines at the start of a source file that are searched for
+/// copyright statements, as they are expected to be in the file's header
+const HEADER_LINES: usize = 50;
+
+/// The end of a license in a file that contains multiple licenses, without
+/// the copyright statements of the license that follows it, as askalono's
+/// line ranges can extend past the license text into them
+fn copyright_end(contents: &str, range: std::ops::Range<usize>) -> usize {
+    let mut end = range.end;
+    for line in contents[range].split_inclusive('\n').rev() {
+        if !line.trim().is_empty() && copyright::extract(line).is_empty() {
+            break;
+        }
+
+        end -= line.len();
+    }
+
+    end
+}
+
+fn gather_copyrights(cfg: &config::Config, licensed_krates: &mut [KrateLicense<'_>]) {
+    licensed_krates.par_iter_mut().for_each(|kl| {
+        let copyrights = kl.license_files.iter().flat_map(|lf| match &lf.kind {
+            LicenseFileKind::Text(_) | LicenseFileKind::AddendumText(..) => {
+                kl.license_file_copyrights(lf)
+            }
+            LicenseFileKind::Header if cfg.header_copyrights => {
+                // Never resolve a relative path against the current directory,
+                // which would read a file from an entirely different crate
+                let path = kl.krate.manifest_path.parent().unwrap().join(&lf.path);
+
+                match std::fs::read_to_string(&path) {
+                    Ok(contents) => {
+                        let end = contents
+                            .match_indices('\n')
+                            .nth(HEADER_LINES - 1)
+                            .map_or(contents.len(), |(i, _)| i);
+                        copyright::extract(&contents[..end])
+                    }
+                    Err(e) => {
+                        log::debug!("unable to read '{}': {}", path, e);
+                        Vec::new()
+                    }
+                }
+            }
+            LicenseFileKind::Header => Vec::new(),
+        });
+
+        kl.copyrights = copyright::merge(copyrights);
+    });
 }
 
-pub(crate) fn apply_clarification<'krate>(
+pub(crate) fn apply_clarification(
     git_cache: &fetch::GitCache,
-    krate: &'krate crate::Krate,
+    krate: &crate::Krate,
     clarification: &config::Clarification,
+    provenance: &Provenance,
 ) -> anyhow::Result<Vec<LicenseFile>> {
     anyhow::ensure!(
         !clarification.files.is_empty() || !clarification.git.is_empty(),
@@ -501,7 +1166,7 @@ pub(crate) fn apply_clarification<'krate>(
 
     let mut lic_files = Vec::with_capacity(clarification.files.len() + clarification.git.len());
 
-    let mut push = |contents: &str, cf: &config::ClarificationFile, license_path| {
+    let mut push = |contents: &str, cf: &config::ClarificationFile, license_path: PathBuf| {
         anyhow::ensure!(
             !contents.is_empty(),
             "clarification file '{}' is empty",
@@ -538,7 +1203,7 @@ pub(crate) fn apply_clarification<'krate>(
         let text = text.to_owned();
 
         lic_files.push(LicenseFile {
-            path: cf.path.clone(),
+            path: license_path,
             confidence: 1.0,
             license_expr: cf
                 .license
@@ -546,6 +1211,8 @@ pub(crate) fn apply_clarification<'krate>(
                 .unwrap_or(&clarification.license)
                 .clone(),
             kind: LicenseFileKind::Text(text),
+            range: (cf.start.is_some() || cf.end.is_some()).then(|| start..end),
+            provenance: provenance.clone(),
         });
 
         Ok(())
@@ -563,7 +1230,7 @@ pub(crate) fn apply_clarification<'krate>(
         let license_path = &file.path;
 
         let contents = git_cache
-            .retrieve(krate, file, &clarification.override_git_commit)
+            .retrieve(krate, &file.path, &clarification.override_git_commit)
             .with_context(|| {
                 format!(
                     "unable to retrieve '{}' for crate '{}' from remote git host",
@@ -585,3 +1252,251 @@ pub fn binary_search<'krate>(
     kl.binary_search_by(|k| k.krate.cmp(krate))
         .map(|i| (i, &kl[i]))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A single path crate in a temporary directory that is removed when
+    /// dropped
+    struct Fixture {
+        root: PathBuf,
+        krates: Krates,
+    }
+
+    impl Fixture {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let root = PathBuf::from_path_buf(std::env::temp_dir())
+                .unwrap()
+                .join(format!(
+                    "cargo-about-gather-{}-{}",
+                    name,
+                    std::process::id()
+                ));
+            let _ = std::fs::remove_dir_all(&root);
+
+            for (path, contents) in files.iter().chain(std::iter::once(&(
+                "Cargo.toml",
+                "[package]\nname = \"krate\"\n",
+            ))) {
+                let path = root.join(path);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, contents).unwrap();
+            }
+
+            let id = format!("krate 0.1.0 (path+file://{})", root);
+            let md = serde_json::json!({
+                "packages": [{
+                    "name": "krate",
+                    "version": "0.1.0",
+                    "id": id,
+                    "source": null,
+                    "license": "MIT",
+                    "dependencies": [],
+                    "targets": [],
+                    "features": {},
+                    "manifest_path": root.join("Cargo.toml"),
+                }],
+                "workspace_members": [id],
+                "resolve": {
+                    "nodes": [{ "id": id, "dependencies": [], "deps": [], "features": [] }],
+                    "root": id,
+                },
+                "workspace_root": root,
+                "target_directory": root.join("target"),
+                "version": 1,
+            });
+
+            let krates = krates::Builder::new()
+                .build_with_metadata(serde_json::from_value(md).unwrap(), |_| {})
+                .unwrap();
+
+            Self { root, krates }
+        }
+
+        fn gather(&self, cfg: &config::Config) -> Vec<KrateLicense<'_>> {
+            Gatherer::with_store(
+                Arc::new(store_from_cache().unwrap()),
+                cd::client::Client::new(),
+            )
+            .with_network(fetch::Network::new(fetch::NetworkMode::Offline))
+            .gather(&self.krates, cfg)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn config(cfg: &str) -> config::Config {
+        toml::from_str(cfg).unwrap()
+    }
+
+    fn text(license: &str) -> &'static str {
+        spdx::license_id(license).unwrap().text()
+    }
+
+    #[test]
+    fn keeps_additional_license_files() {
+        let fixture = Fixture::new(
+            "additional",
+            &[
+                ("LICENSE", text("MIT")),
+                ("vendor/lib/terms.txt", text("MIT")),
+            ],
+        );
+
+        let gathered = fixture.gather(&config(
+            r#"
+accepted = ["MIT"]
+
+[krate]
+additional = [{ root = "vendor/lib", license = "MIT", license-file = "vendor/lib/terms.txt" }]
+"#,
+        ));
+
+        let mut files: Vec<_> = gathered[0]
+            .license_files
+            .iter()
+            .map(|lf| {
+                (
+                    lf.path.strip_prefix(&fixture.root).unwrap().as_str(),
+                    lf.license_expr.to_string(),
+                    matches!(lf.kind, LicenseFileKind::AddendumText(..)),
+                )
+            })
+            .collect();
+        files.sort();
+
+        assert_eq!(
+            files,
+            vec![
+                ("LICENSE", "MIT".to_owned(), false),
+                ("vendor/lib/terms.txt", "MIT".to_owned(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_every_mismatched_header() {
+        const GPL_HEADER: &str = "\
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+pub fn vendored() {}
+";
+
+        let fixture = Fixture::new(
+            "headers",
+            &[
+                ("LICENSE", text("MIT")),
+                ("src/lib.rs", "pub mod a;\npub mod b;\n"),
+                ("src/a.rs", GPL_HEADER),
+                ("src/b.rs", GPL_HEADER),
+            ],
+        );
+
+        let mismatches = |mode: &str| {
+            let cfg = config(&format!(
+                "accepted = [\"MIT\"]\nheader-mismatch = \"deny\"\n[scan]\nmode = \"{}\"\n",
+                mode
+            ));
+            let gathered = fixture.gather(&cfg);
+            let (_, resolved) = resolution::resolve(&gathered, &cfg);
+
+            resolved[0]
+                .as_ref()
+                .unwrap()
+                .diagnostics
+                .iter()
+                .filter(|diag| diag.message.starts_with("license header"))
+                .map(|diag| (diag.message.clone(), diag.notes.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        // Source files are only scanned in deep mode
+        assert!(mismatches("fast").is_empty());
+
+        let deep = mismatches("deep");
+        assert_eq!(deep.len(), 1, "{:?}", deep);
+        assert!(deep[0].0.contains("'GPL-3.0"), "{}", deep[0].0);
+        assert_eq!(
+            deep[0]
+                .1
+                .iter()
+                .map(|note| note.split(' ').next().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["src/a.rs", "src/b.rs"]
+        );
+    }
+
+    #[test]
+    fn extracts_copyrights_of_segmented_licenses() {
+        // The canonical texts have their own copyrights
+        let text = |id: &str| {
+            text(id)
+                .lines()
+                .filter(|line| !line.starts_with("Copyright"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        // Laid out like ring's LICENSE, where each copyright precedes the
+        // license it applies to
+        let contents = format!(
+            "Copyright 2015-2016 Brian Smith.\n\n{}\n\n\
+            Copyright (c) 2015-2020 the fiat-crypto authors.\n\n{}",
+            text("ISC"),
+            text("MIT"),
+        );
+
+        let fixture = Fixture::new("copyrights", &[("LICENSE", &contents)]);
+        let gathered = fixture.gather(&config("accepted = [\"MIT\"]"));
+        let kl = &gathered[0];
+
+        let mut holders: Vec<_> = kl
+            .license_files
+            .iter()
+            .map(|lf| {
+                assert!(lf.range.is_some(), "{} was not segmented", lf.license_expr);
+
+                (
+                    lf.license_expr.to_string(),
+                    kl.license_file_copyrights(lf)
+                        .into_iter()
+                        .map(|c| c.holder)
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        holders.sort();
+
+        assert_eq!(
+            holders,
+            vec![
+                ("ISC".to_owned(), vec!["Brian Smith".to_owned()]),
+                ("MIT".to_owned(), vec!["the fiat-crypto authors".to_owned()]),
+            ]
+        );
+
+        assert_eq!(
+            kl.copyrights
+                .iter()
+                .map(|c| c.holder.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Brian Smith", "the fiat-crypto authors"]
+        );
+    }
+}
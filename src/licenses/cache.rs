@@ -0,0 +1,874 @@
+use super::config;
+use crate::Krate;
+use anyhow::Context as _;
+use krates::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The name of the directory, relative to `CARGO_HOME`, that is used as the
+/// cache root if one is not specified in the config
+const DEFAULT_DIR: &str = "cargo-about";
+
+/// Gets the current time as seconds since the unix epoch
+#[inline]
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// A persistent, on-disk cache of data that is expensive (or impossible when
+/// offline) to retrieve, so that it can be reused between runs
+#[derive(Clone)]
+pub struct Cache {
+    root: PathBuf,
+    clearly_defined_ttl: u64,
+}
+
+impl Cache {
+    /// Creates a cache from the user's configuration, returning `None` if the
+    /// cache has been disabled
+    pub fn from_config(cfg: &config::Cache) -> anyhow::Result<Option<Self>> {
+        if cfg.disabled {
+            return Ok(None);
+        }
+
+        let root = match &cfg.path {
+            Some(path) => path.clone(),
+            None => {
+                let cargo_home =
+                    home::cargo_home().context("unable to find CARGO_HOME directory")?;
+                PathBuf::from_path_buf(cargo_home)
+                    .map_err(|pb| {
+                        anyhow::anyhow!("CARGO_HOME '{}' is not a utf-8 path", pb.display())
+                    })?
+                    .join(DEFAULT_DIR)
+            }
+        };
+
+        Ok(Some(Self {
+            root,
+            clearly_defined_ttl: cfg.clearly_defined_ttl,
+        }))
+    }
+
+    /// The root directory of the cache
+    #[inline]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    #[inline]
+    fn entry_path(&self, kind: &str, key: &str) -> PathBuf {
+        let mut path = self.root.join(kind);
+        path.push(format!("{}.json", key));
+        path
+    }
+
+    /// Reads an entry from the cache, any failures are treated as a cache miss
+    pub(crate) fn read<T: DeserializeOwned>(&self, kind: &str, key: &str) -> Option<T> {
+        let path = self.entry_path(kind, key);
+
+        let contents = match std::fs::read(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                log::warn!("failed to read cache entry '{}': {}", path, e);
+                return None;
+            }
+        };
+
+        match serde_json::from_slice(&contents) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                log::warn!("failed to deserialize cache entry '{}': {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Writes an entry to the cache. The entry is first written to a temporary
+    /// file and then moved into place so that concurrent runs never observe
+    /// a partially written entry
+    pub(crate) fn write<T: Serialize>(
+        &self,
+        kind: &str,
+        key: &str,
+        entry: &T,
+    ) -> anyhow::Result<()> {
+        let path = self.entry_path(kind, key);
+        let dir = path.parent().unwrap();
+
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("unable to create cache directory '{}'", dir))?;
+
+        let serialized = serde_json::to_vec(entry).context("failed to serialize cache entry")?;
+
+        let tmp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        std::fs::write(&tmp_path, serialized)
+            .with_context(|| format!("unable to write cache entry '{}'", tmp_path))?;
+        std::fs::rename(&tmp_path, &path)
+            .with_context(|| format!("unable to move cache entry into place '{}'", path))?;
+
+        Ok(())
+    }
+
+    /// Retrieves the cached clearlydefined.io definition for the crate, as long
+    /// as it was retrieved within the configured TTL. When `offline` is true
+    /// the TTL is ignored, as stale data is preferable to no data
+    pub(crate) fn clearly_defined(
+        &self,
+        name: &str,
+        version: &krates::semver::Version,
+        offline: bool,
+    ) -> Option<Definition> {
+        let def: Definition = self.read(CLEARLY_DEFINED, &Definition::key(name, version))?;
+
+        if !offline && now().saturating_sub(def.retrieved) > self.clearly_defined_ttl {
+            log::debug!("cached definition for '{} {}' has expired", name, version);
+            return None;
+        }
+
+        Some(def)
+    }
+
+    /// Stores a definition retrieved from clearlydefined.io, replacing any
+    /// cached definition from a different harvest
+    pub(crate) fn store_clearly_defined(&self, def: &Definition) {
+        let key = Definition::key(&def.name, &def.version);
+
+        if let Some(cached) = self.read::<Definition>(CLEARLY_DEFINED, &key) {
+            if cached.harvested.is_some() && cached.harvested != def.harvested {
+                log::info!(
+                    "'{} {}' was harvested again by clearlydefined.io, replacing the cached definition",
+                    def.name,
+                    def.version
+                );
+            }
+        }
+
+        if let Err(e) = self.write(CLEARLY_DEFINED, &key, def) {
+            log::warn!("failed to cache definition for '{}': {:#}", key, e);
+        }
+    }
+
+    /// Retrieves a file previously fetched from a git repository, validating
+    /// that its contents still match the checksum recorded when it was stored
+    pub(crate) fn git_file(&self, repo: &str, rev: &str, path: &Path) -> Option<String> {
+        let key = GitFile::key(repo, rev, path);
+        let file: GitFile = self.read(GIT, &key)?;
+
+        if let Err(e) = crate::validate_sha256(&file.contents, &file.sha256) {
+            log::warn!(
+                "cached '{}' from repo '{}' @ {} is corrupt: {:#}",
+                path,
+                repo,
+                rev,
+                e
+            );
+            return None;
+        }
+
+        Some(file.contents)
+    }
+
+    pub(crate) fn store_git_file(&self, repo: &str, rev: &str, path: &Path, contents: &str) {
+        let key = GitFile::key(repo, rev, path);
+
+        let sha256 = {
+            let digest = ring::digest::digest(&ring::digest::SHA256, contents.as_bytes());
+            crate::to_hex(digest.as_ref())
+        };
+
+        let file = GitFile {
+            repo: repo.to_owned(),
+            rev: rev.to_owned(),
+            path: path.to_owned(),
+            sha256,
+            contents: contents.to_owned(),
+        };
+
+        if let Err(e) = self.write(GIT, &key, &file) {
+            log::warn!(
+                "failed to cache '{}' from repo '{}' @ {}: {:#}",
+                path,
+                repo,
+                rev,
+                e
+            );
+        }
+    }
+
+    /// Retrieves the license files previously detected by scanning the crate,
+    /// validating that the text of every license file still matches the
+    /// checksum recorded when it was scanned
+    pub(crate) fn scan(&self, krate: &Krate, key: &str) -> Option<Vec<super::LicenseFile>> {
+        let scan: Scan = self.read(SCAN, key)?;
+        let root = krate.manifest_path.parent().unwrap();
+
+        scan.files
+            .into_iter()
+            .map(|file| {
+                let path = root.join(&file.path);
+
+                let license_expr = match spdx::Expression::parse(&file.license) {
+                    Ok(expr) => expr,
+                    Err(e) => {
+                        log::warn!("cached scan of '{}' has an invalid license: {}", path, e);
+                        return None;
+                    }
+                };
+
+                let kind = match file.sha256 {
+                    Some(sha256) => {
+                        let mut text = std::fs::read_to_string(&path).ok()?;
+                        if let Some(range) = &file.range {
+                            text = text.get(range.clone())?.to_owned();
+                        }
+
+                        if let Err(e) = crate::validate_sha256(&text, &sha256) {
+                            log::debug!("cached scan of '{}' is out of date: {:#}", path, e);
+                            return None;
+                        }
+
+                        super::LicenseFileKind::Text(text)
+                    }
+                    None => super::LicenseFileKind::Header,
+                };
+
+                Some(super::LicenseFile {
+                    license_expr,
+                    path,
+                    confidence: file.confidence,
+                    kind,
+                    range: file.range,
+                    provenance: super::Provenance::FileSystem,
+                })
+            })
+            .collect()
+    }
+
+    pub(crate) fn store_scan(
+        &self,
+        krate: &Krate,
+        key: &str,
+        checksum: &str,
+        files: &[super::LicenseFile],
+    ) {
+        let root = krate.manifest_path.parent().unwrap();
+
+        let files = files
+            .iter()
+            .map(|lf| ScannedFile {
+                path: lf.path.strip_prefix(root).unwrap_or(&lf.path).to_owned(),
+                license: lf.license_expr.to_string(),
+                confidence: lf.confidence,
+                sha256: match &lf.kind {
+                    super::LicenseFileKind::Text(text)
+                    | super::LicenseFileKind::AddendumText(text, _) => {
+                        let digest = ring::digest::digest(&ring::digest::SHA256, text.as_bytes());
+                        Some(crate::to_hex(digest.as_ref()))
+                    }
+                    super::LicenseFileKind::Header => None,
+                },
+                range: lf.range.clone(),
+            })
+            .collect();
+
+        let scan = Scan {
+            name: krate.name.clone(),
+            version: krate.version.to_string(),
+            checksum: checksum.to_owned(),
+            files,
+        };
+
+        if let Err(e) = self.write(SCAN, key, &scan) {
+            log::warn!("failed to cache scan of '{}': {:#}", krate, e);
+        }
+    }
+
+    /// Gets every entry currently in the cache
+    pub fn entries(&self) -> anyhow::Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+
+        for kind in KINDS {
+            let dir = self.root.join(kind);
+
+            let read_dir = match std::fs::read_dir(&dir) {
+                Ok(rd) => rd,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    return Err(e).with_context(|| format!("unable to read directory '{}'", dir))
+                }
+            };
+
+            for dir_entry in read_dir {
+                let dir_entry =
+                    dir_entry.with_context(|| format!("unable to read directory '{}'", dir))?;
+
+                let path = match PathBuf::from_path_buf(dir_entry.path()) {
+                    Ok(pb) => pb,
+                    Err(pb) => {
+                        log::warn!("skipping non utf-8 path {}", pb.display());
+                        continue;
+                    }
+                };
+
+                let key = match (path.extension(), path.file_stem()) {
+                    (Some("json"), Some(stem)) => stem.to_owned(),
+                    _ => continue,
+                };
+
+                let md = dir_entry
+                    .metadata()
+                    .with_context(|| format!("unable to read metadata for '{}'", path))?;
+
+                let modified = md
+                    .modified()
+                    .ok()
+                    .and_then(|m| m.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map_or(0, |d| d.as_secs());
+
+                let description = match *kind {
+                    GIT => self
+                        .read::<GitFile>(GIT, &key)
+                        .map(|gf| format!("{} @ {} '{}'", gf.repo, gf.rev, gf.path)),
+                    SCAN => self
+                        .read::<Scan>(SCAN, &key)
+                        .map(|scan| format!("{} {}", scan.name, scan.version)),
+                    _ => None,
+                };
+
+                entries.push(Entry {
+                    kind,
+                    key,
+                    path,
+                    size: md.len(),
+                    modified,
+                    description,
+                });
+            }
+        }
+
+        entries.sort_by(|a, b| a.kind.cmp(b.kind).then_with(|| a.key.cmp(&b.key)));
+        Ok(entries)
+    }
+
+    /// Removes entries from the cache. If `older_than` is specified, only
+    /// entries that were last written more than that many seconds ago are
+    /// removed. Returns the entries that were removed.
+    pub fn prune(&self, older_than: Option<u64>) -> anyhow::Result<Vec<Entry>> {
+        let now = now();
+        let mut pruned = Vec::new();
+
+        for entry in self.entries()? {
+            if let Some(older_than) = older_than {
+                if now.saturating_sub(entry.modified) <= older_than {
+                    continue;
+                }
+            }
+
+            std::fs::remove_file(&entry.path)
+                .with_context(|| format!("unable to remove '{}'", entry.path))?;
+            pruned.push(entry);
+        }
+
+        Ok(pruned)
+    }
+}
+
+const CLEARLY_DEFINED: &str = "clearly-defined";
+const GIT: &str = "git";
+const SCAN: &str = "scan";
+const KINDS: &[&str] = &[CLEARLY_DEFINED, GIT, SCAN];
+
+/// A single entry in the cache
+pub struct Entry {
+    /// The kind of data in the entry
+    pub kind: &'static str,
+    /// The unique key of the entry
+    pub key: String,
+    /// The full path of the entry on disk
+    pub path: PathBuf,
+    /// The size of the entry, in bytes
+    pub size: u64,
+    /// The time the entry was last written, in seconds since the unix epoch
+    pub modified: u64,
+    /// A human readable description of the entry, if the key isn't one
+    pub description: Option<String>,
+}
+
+/// A file retrieved from a git repository. Since the file is retrieved from
+/// a specific commit, it never needs to be invalidated
+#[derive(Serialize, Deserialize)]
+struct GitFile {
+    /// The url of the repository
+    repo: String,
+    /// The revision the file was retrieved from
+    rev: String,
+    /// The repository relative path of the file
+    path: PathBuf,
+    /// The sha-256 checksum of the contents, in hex
+    sha256: String,
+    /// The contents of the file
+    contents: String,
+}
+
+impl GitFile {
+    /// The key is content addressed by the repository, revision and path
+    fn key(repo: &str, rev: &str, path: &Path) -> String {
+        use std::hash::Hasher;
+        let mut hasher = twox_hash::XxHash64::default();
+
+        hasher.write(repo.as_bytes());
+        hasher.write(rev.as_bytes());
+        hasher.write(path.as_str().as_bytes());
+
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// A license file detected by scanning a crate
+#[derive(Serialize, Deserialize)]
+struct ScannedFile {
+    /// The crate relative path of the file
+    path: PathBuf,
+    /// The license expression detected for the file
+    license: String,
+    /// The confidence score of the detected license
+    confidence: f32,
+    /// The sha-256 checksum of the license text, if it is license text rather
+    /// than just a license header
+    sha256: Option<String>,
+    /// The byte range of the license text, if it is only part of the file
+    #[serde(default)]
+    range: Option<std::ops::Range<usize>>,
+}
+
+/// The license files detected by scanning a registry crate. Since registry
+/// crates are immutable, the scan only needs to be invalidated if the way
+/// the crate is scanned changes
+#[derive(Serialize, Deserialize)]
+struct Scan {
+    /// The name of the crate
+    name: String,
+    /// The version of the crate
+    version: String,
+    /// The checksum of the crate package from the Cargo.lock
+    checksum: String,
+    /// The license files that were detected
+    files: Vec<ScannedFile>,
+}
+
+/// The key a scan is cached under, which is unique to the crate's package
+/// checksum and the `params` that affect the results of the scan
+pub(crate) fn scan_key(krate: &Krate, checksum: &str, params: u64) -> String {
+    use std::hash::Hasher;
+    let mut hasher = twox_hash::XxHash64::with_seed(params);
+    hasher.write(checksum.as_bytes());
+
+    format!("{}-{}-{:016x}", krate.name, krate.version, hasher.finish())
+}
+
+#[derive(Deserialize)]
+struct LockPackage {
+    name: String,
+    version: String,
+    source: Option<String>,
+    checksum: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Lockfile {
+    #[serde(default)]
+    package: Vec<LockPackage>,
+}
+
+/// Reads the package checksum of every registry crate in a Cargo.lock, which
+/// can be looked up with [`checksum`]
+pub(crate) fn registry_checksums(lock_path: &Path) -> anyhow::Result<HashMap<String, String>> {
+    let contents = std::fs::read_to_string(lock_path)
+        .with_context(|| format!("unable to read '{}'", lock_path))?;
+    let lockfile: Lockfile =
+        toml::from_str(&contents).with_context(|| format!("unable to parse '{}'", lock_path))?;
+
+    Ok(lockfile
+        .package
+        .into_iter()
+        .filter_map(|pkg| {
+            let key = format!("{} {} ({})", pkg.name, pkg.version, pkg.source?);
+            Some((key, pkg.checksum?))
+        })
+        .collect())
+}
+
+/// Gets the package checksum of the crate, if it is from a registry
+pub(crate) fn checksum<'c>(
+    checksums: &'c HashMap<String, String>,
+    krate: &Krate,
+) -> Option<&'c str> {
+    let source = krate.source.as_ref()?;
+    checksums
+        .get(&format!(
+            "{} {} ({})",
+            krate.name, krate.version, source.repr
+        ))
+        .map(String::as_str)
+}
+
+/// A single file in a [`Definition`]
+#[derive(Serialize, Deserialize)]
+pub struct DefinitionFile {
+    /// The crate relative path of the file
+    pub path: PathBuf,
+    /// The sha-256 checksum of the file when it was harvested
+    pub sha256: Option<String>,
+    /// The license that was discovered for the file
+    pub license: Option<String>,
+    /// Whether clearlydefined thinks the file is a license file
+    pub is_license: bool,
+}
+
+/// The subset of a clearlydefined.io definition that we actually use, in a
+/// form that can be persisted to disk
+#[derive(Serialize, Deserialize)]
+pub struct Definition {
+    /// The name of the crate
+    pub name: String,
+    /// The version of the crate
+    pub version: String,
+    /// The harvest tools, and their versions, that were used to harvest the
+    /// definition. A new harvest will change this
+    pub harvest: Vec<String>,
+    /// The time the definition was last harvested, as reported by
+    /// clearlydefined.io. A new harvest will change this
+    pub harvested: Option<String>,
+    /// The effective score of the definition, 0-100
+    pub score: u8,
+    /// The files crawled when the definition was harvested
+    pub files: Vec<DefinitionFile>,
+    /// The time the definition was retrieved, in seconds since the unix epoch
+    pub retrieved: u64,
+    /// clearlydefined.io has not harvested the crate, this is cached like any
+    /// other definition so that it isn't requested again until the TTL expires,
+    /// and so that `--frozen` runs don't need to request it either
+    pub unharvested: bool,
+}
+
+impl Definition {
+    #[inline]
+    fn key(name: &str, version: &dyn std::fmt::Display) -> String {
+        format!("{}-{}", name, version)
+    }
+
+    /// A definition for a crate that clearlydefined.io has not harvested
+    pub(crate) fn unharvested(name: &str, version: &dyn std::fmt::Display) -> Self {
+        Self {
+            name: name.to_owned(),
+            version: version.to_string(),
+            harvest: Vec::new(),
+            harvested: None,
+            score: 0,
+            files: Vec::new(),
+            retrieved: now(),
+            unharvested: true,
+        }
+    }
+
+    /// Converts a definition retrieved from clearlydefined.io, returning `None`
+    /// if the definition isn't for a valid crate version
+    pub(crate) fn from_cd(
+        def: cd::definitions::Definition,
+        harvested: Option<String>,
+    ) -> Option<Self> {
+        // Since we only ever retrieve license information for crates on crates.io
+        // they _should_ always have a valid semver
+        let version = match &def.coordinates.revision {
+            cd::CoordVersion::Semver(vers) => vers.to_string(),
+            cd::CoordVersion::Any(vers) => {
+                log::warn!(
+                    "the definition for {} does not have a valid semver '{}'",
+                    def.coordinates,
+                    vers,
+                );
+                return None;
+            }
+        };
+
+        let described = match def.described {
+            Some(described) => described,
+            None => {
+                log::warn!(
+                    "the definition for {} has not been harvested",
+                    def.coordinates
+                );
+                return Some(Self::unharvested(&def.coordinates.name, &version));
+            }
+        };
+
+        Some(Self {
+            name: def.coordinates.name,
+            version,
+            harvest: described.tools,
+            harvested,
+            score: def.scores.effective,
+            files: def
+                .files
+                .into_iter()
+                .map(|file| DefinitionFile {
+                    is_license: file.natures.iter().any(|s| s == "license"),
+                    path: file.path,
+                    sha256: file.hashes.and_then(|hashes| hashes.sha256),
+                    license: file.license,
+                })
+                .collect(),
+            retrieved: now(),
+            unharvested: false,
+        })
+    }
+}
+
+/// The response to a clearlydefined.io definitions request. Unlike
+/// [`cd::definitions::GetResponse`] this retains the time each definition was
+/// last harvested, which is only present in the `_meta` of the definition
+pub(crate) struct GetResponse {
+    /// The harvested definitions, one for each coordinate passed to the request
+    pub(crate) definitions: Vec<Definition>,
+}
+
+impl cd::ApiResponse<bytes::Bytes> for GetResponse {}
+
+impl TryFrom<http::Response<bytes::Bytes>> for GetResponse {
+    type Error = cd::Error;
+
+    fn try_from(response: http::Response<bytes::Bytes>) -> Result<Self, Self::Error> {
+        let items: std::collections::BTreeMap<String, serde_json::Value> =
+            serde_json::from_slice(response.body())?;
+
+        let mut definitions = Vec::with_capacity(items.len());
+        for mut item in items.into_values() {
+            let harvested = item
+                .pointer("/_meta/updated")
+                .and_then(|updated| updated.as_str())
+                .map(String::from);
+
+            // The definition can only be deserialized from borrowed input, and
+            // fails on fields it doesn't know about, such as `_meta`
+            if let Some(fields) = item.as_object_mut() {
+                fields.retain(|field, _| {
+                    matches!(
+                        field.as_str(),
+                        "coordinates" | "described" | "licensed" | "files" | "scores"
+                    )
+                });
+            }
+            let def = serde_json::from_slice(&serde_json::to_vec(&item)?)?;
+
+            if let Some(def) = Definition::from_cd(def, harvested) {
+                definitions.push(def);
+            }
+        }
+
+        Ok(Self { definitions })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use krates::semver::Version;
+
+    /// Creates a cache in a unique temporary directory, which is removed when
+    /// the returned guard is dropped
+    struct TempCache(Cache);
+
+    impl TempCache {
+        fn new(name: &str, clearly_defined_ttl: u64) -> Self {
+            let root = PathBuf::from_path_buf(std::env::temp_dir())
+                .unwrap()
+                .join(format!("cargo-about-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+
+            Self(Cache {
+                root,
+                clearly_defined_ttl,
+            })
+        }
+    }
+
+    impl Drop for TempCache {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0.root);
+        }
+    }
+
+    fn definition(harvested: Option<&str>, retrieved: u64) -> Definition {
+        Definition {
+            name: "syn".to_owned(),
+            version: "1.0.14".to_owned(),
+            harvest: vec!["licensee/9.13.0".to_owned()],
+            harvested: harvested.map(String::from),
+            score: 87,
+            files: vec![DefinitionFile {
+                path: "LICENSE-MIT".into(),
+                sha256: Some("abcd".to_owned()),
+                license: Some("MIT".to_owned()),
+                is_license: true,
+            }],
+            retrieved,
+            unharvested: false,
+        }
+    }
+
+    #[test]
+    fn reads_and_writes_definitions() {
+        let cache = TempCache::new("definitions", 60);
+        let version = Version::new(1, 0, 14);
+
+        assert!(cache.0.clearly_defined("syn", &version, false).is_none());
+
+        cache
+            .0
+            .store_clearly_defined(&definition(Some("2020-01-23T00:25:22.433Z"), now()));
+
+        let def = cache.0.clearly_defined("syn", &version, false).unwrap();
+        assert_eq!(def.harvested.as_deref(), Some("2020-01-23T00:25:22.433Z"));
+        assert_eq!(def.score, 87);
+        assert_eq!(def.files[0].path, "LICENSE-MIT");
+        assert!(def.files[0].is_license);
+
+        assert!(cache
+            .0
+            .clearly_defined("syn", &Version::new(1, 0, 15), false)
+            .is_none());
+
+        let entries = cache.0.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, CLEARLY_DEFINED);
+
+        assert_eq!(cache.0.prune(None).unwrap().len(), 1);
+        assert!(cache.0.clearly_defined("syn", &version, false).is_none());
+    }
+
+    #[test]
+    fn expires_definitions_unless_offline() {
+        let cache = TempCache::new("expiry", 60);
+        let version = Version::new(1, 0, 14);
+
+        cache
+            .0
+            .store_clearly_defined(&definition(Some("2020-01-23T00:25:22.433Z"), now() - 61));
+
+        assert!(cache.0.clearly_defined("syn", &version, false).is_none());
+        assert!(cache.0.clearly_defined("syn", &version, true).is_some());
+    }
+
+    #[test]
+    fn caches_unharvested_definitions() {
+        let cache = TempCache::new("unharvested", 60);
+        let version = Version::new(0, 1, 0);
+
+        cache
+            .0
+            .store_clearly_defined(&Definition::unharvested("unknown", &version));
+
+        // Crates that haven't been harvested are served from the cache like
+        // any other definition, so frozen runs don't need to request them
+        for offline in [false, true] {
+            let def = cache
+                .0
+                .clearly_defined("unknown", &version, offline)
+                .unwrap();
+            assert!(def.unharvested);
+            assert!(def.files.is_empty());
+        }
+
+        let mut def = Definition::unharvested("unknown", &version);
+        def.retrieved = now() - 61;
+        cache.0.store_clearly_defined(&def);
+
+        assert!(cache
+            .0
+            .clearly_defined("unknown", &version, false)
+            .is_none());
+        assert!(cache.0.clearly_defined("unknown", &version, true).is_some());
+    }
+
+    #[test]
+    fn reads_and_validates_git_files() {
+        let cache = TempCache::new("git", 60);
+        let repo = "https://github.com/EmbarkStudios/cargo-about";
+        let path = Path::new("LICENSE-MIT");
+
+        assert!(cache.0.git_file(repo, "abc", path).is_none());
+
+        cache.0.store_git_file(repo, "abc", path, "license text");
+        assert_eq!(
+            cache.0.git_file(repo, "abc", path).as_deref(),
+            Some("license text")
+        );
+        assert!(cache.0.git_file(repo, "def", path).is_none());
+
+        // Corrupt the contents so they no longer match the checksum
+        let key = GitFile::key(repo, "abc", path);
+        let mut file: GitFile = cache.0.read(GIT, &key).unwrap();
+        file.contents.push_str(" that was modified");
+        cache.0.write(GIT, &key, &file).unwrap();
+
+        assert!(cache.0.git_file(repo, "abc", path).is_none());
+    }
+
+    #[test]
+    fn parses_harvest_time() {
+        let body = r#"{
+            "crate/cratesio/-/syn/1.0.14": {
+                "described": {
+                    "releaseDate": "2020-01-20",
+                    "urls": {},
+                    "hashes": { "sha1": "85b0fe2790310f9d6daf04393bc0cf266841d861" },
+                    "files": 1,
+                    "tools": ["clearlydefined/1.2.0", "licensee/9.13.0"],
+                    "toolScore": { "total": 100, "date": 30, "source": 70 },
+                    "score": { "total": 100, "date": 30, "source": 70 }
+                },
+                "licensed": null,
+                "files": [
+                    {
+                        "path": "LICENSE-MIT",
+                        "hashes": { "sha1": "a", "sha256": "b" },
+                        "license": "MIT",
+                        "natures": ["license"]
+                    }
+                ],
+                "coordinates": { "type": "crate", "provider": "cratesio", "name": "syn", "revision": "1.0.14" },
+                "_meta": { "schemaVersion": "1.6.1", "updated": "2020-01-23T00:25:22.433Z" },
+                "scores": { "effective": 87, "tool": 87 }
+            },
+            "crate/cratesio/-/unknown/0.1.0": {
+                "described": null,
+                "licensed": null,
+                "files": [],
+                "coordinates": { "type": "crate", "provider": "cratesio", "name": "unknown", "revision": "0.1.0" },
+                "scores": { "effective": 0, "tool": 0 }
+            }
+        }"#;
+
+        let response =
+            GetResponse::try_from(http::Response::new(bytes::Bytes::from(body))).unwrap();
+
+        assert_eq!(response.definitions.len(), 2);
+        let def = &response.definitions[0];
+        assert_eq!(def.name, "syn");
+        assert_eq!(def.version, "1.0.14");
+        assert_eq!(def.harvested.as_deref(), Some("2020-01-23T00:25:22.433Z"));
+        assert_eq!(def.score, 87);
+        assert_eq!(def.files[0].sha256.as_deref(), Some("b"));
+        assert!(def.files[0].is_license);
+        assert!(!def.unharvested);
+
+        let def = &response.definitions[1];
+        assert_eq!(def.name, "unknown");
+        assert_eq!(def.version, "0.1.0");
+        assert!(def.unharvested);
+    }
+}
@@ -1,7 +1,7 @@
 use krates::Utf8PathBuf as PathBuf;
 use serde::{de, ser, Deserialize, Serialize};
 use spdx::Expression;
-use std::{collections::BTreeMap, fmt};
+use std::fmt;
 
 mod spdx_expr {
     use super::*;
@@ -89,27 +89,50 @@ where
     deserializer.deserialize_seq(Visitor)
 }
 
+/// A license file that applies to a subset of a crate, eg. vendored code,
+/// that wouldn't otherwise be detected
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct Additional {
+    /// The crate relative path of the directory the license applies to
     pub root: PathBuf,
+    /// The license expression of the file
     #[serde(with = "spdx_expr")]
     pub license: Expression,
+    /// The crate relative path to the file
     pub license_file: PathBuf,
+    /// The 1-based line the license text starts on, defaults to the first line
     pub license_start: Option<usize>,
+    /// The 1-based, inclusive, line the license text ends on, defaults to the
+    /// last line
     pub license_end: Option<usize>,
 }
 
+/// A license file that was detected in a crate, but should not be included,
+/// eg. test fixtures that contain license text
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct Ignore {
+    /// The license that was detected in the file
     #[serde(with = "spdx_expr")]
     pub license: Expression,
+    /// The crate relative path to the file
     pub license_file: PathBuf,
+    /// The 1-based line the ignored text starts on, defaults to the first line
     pub license_start: Option<usize>,
+    /// The 1-based, inclusive, line the ignored text ends on, defaults to the
+    /// last line
     pub license_end: Option<usize>,
 }
 
+impl Ignore {
+    /// Whether only a subset of the lines in the file are ignored
+    #[inline]
+    pub fn is_partial(&self) -> bool {
+        self.license_start.is_some() || self.license_end.is_some()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct ClarificationFile {
@@ -164,6 +187,14 @@ pub struct Clarification {
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct KrateConfig {
+    /// The name of the crate, taken from the key of the entry
+    #[serde(skip)]
+    pub name: String,
+    /// The versions of the crate this configuration applies to, defaults to
+    /// every version if not specified. Can also be specified in the key of the
+    /// entry, eg. `["ring@0.16"]`
+    #[serde(default)]
+    pub version: Option<krates::semver::VersionReq>,
     /// The list of additional accepted licenses for this crate, again in
     /// priority order
     #[serde(default, deserialize_with = "deserialize_licensee")]
@@ -171,6 +202,173 @@ pub struct KrateConfig {
     /// Overrides the license expression for a crate as long as 1 or more file
     /// checksums match
     pub clarify: Option<Clarification>,
+    /// License files that are included in addition to the ones detected in
+    /// the crate
+    #[serde(default)]
+    pub additional: Vec<Additional>,
+    /// License files detected in the crate that are ignored
+    #[serde(default)]
+    pub ignore: Vec<Ignore>,
+    /// Configures which files are scanned for license information, in
+    /// addition to the global configuration
+    #[serde(default)]
+    pub scan: Scan,
+}
+
+impl KrateConfig {
+    /// Whether the configuration applies to the specified crate
+    #[inline]
+    pub fn matches(&self, krate: &crate::Krate) -> bool {
+        self.name == krate.name
+            && self
+                .version
+                .as_ref()
+                .map_or(true, |vr| vr.matches(&krate.version))
+    }
+}
+
+/// The configuration for a crate can either be a single table, or an array of
+/// tables, each scoped to a different range of versions
+struct KrateConfigs(Vec<KrateConfig>);
+
+impl<'de> Deserialize<'de> for KrateConfigs {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = KrateConfigs;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a crate configuration table, or an array of them")
+            }
+
+            fn visit_map<M>(self, map: M) -> Result<Self::Value, M::Error>
+            where
+                M: de::MapAccess<'de>,
+            {
+                KrateConfig::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(|kc| KrateConfigs(vec![kc]))
+            }
+
+            fn visit_seq<S>(self, seq: S) -> Result<Self::Value, S::Error>
+            where
+                S: de::SeqAccess<'de>,
+            {
+                Vec::deserialize(de::value::SeqAccessDeserializer::new(seq)).map(KrateConfigs)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// The entries of a table, in the order they were declared
+struct Entries<V>(Vec<(String, V)>);
+
+impl<'de, V: Deserialize<'de>> Deserialize<'de> for Entries<V> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct Visitor<V>(std::marker::PhantomData<V>);
+
+        impl<'de, V: Deserialize<'de>> de::Visitor<'de> for Visitor<V> {
+            type Value = Entries<V>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a table")
+            }
+
+            fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+            where
+                M: de::MapAccess<'de>,
+            {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(Entries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(Visitor(std::marker::PhantomData))
+    }
+}
+
+/// Deserializes the crate specific configuration, where each key is either
+/// the name of a crate, or the name of a crate and a version requirement
+/// separated by `@`. The configurations are kept in the order they were
+/// declared, as eg. the first clarification that applies to a crate is used.
+fn deserialize_krate_configs<'de, D>(deserializer: D) -> Result<Vec<KrateConfig>, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    let Entries(entries) = Entries::<KrateConfigs>::deserialize(deserializer)?;
+
+    let mut krate_configs = Vec::new();
+    for (key, KrateConfigs(kcs)) in entries {
+        let (name, version) = match key.split_once('@') {
+            Some((name, vr)) => (
+                name,
+                Some(krates::semver::VersionReq::parse(vr).map_err(|e| {
+                    de::Error::custom(format!(
+                        "'{}' has an invalid version requirement: {}",
+                        key, e
+                    ))
+                })?),
+            ),
+            None => (key.as_str(), None),
+        };
+
+        for mut kc in kcs {
+            if version.is_some() {
+                if kc.version.is_some() {
+                    return Err(de::Error::custom(format!(
+                        "'{}' specifies a version requirement in both its key and its `version` field",
+                        key
+                    )));
+                }
+
+                kc.version = version.clone();
+            }
+
+            kc.name = name.to_owned();
+            krate_configs.push(kc);
+        }
+    }
+
+    Ok(krate_configs)
+}
+
+/// Allows licenses for a specific crate, optionally only for a range of its
+/// versions, even if they are denied or not otherwise accepted
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Exception {
+    /// The name of the crate the exception applies to
+    pub name: String,
+    /// The versions of the crate the exception applies to, defaults to every
+    /// version if not specified
+    #[serde(default)]
+    pub version: Option<krates::semver::VersionReq>,
+    /// The licenses that are allowed for the crate
+    #[serde(deserialize_with = "deserialize_licensee")]
+    pub allow: Vec<spdx::Licensee>,
+}
+
+impl Exception {
+    /// Whether the exception applies to the specified crate
+    #[inline]
+    pub fn matches(&self, krate: &crate::Krate) -> bool {
+        self.name == krate.name
+            && self
+                .version
+                .as_ref()
+                .map_or(true, |vr| vr.matches(&krate.version))
+    }
 }
 
 /// Configures how private crates are handled and detected
@@ -188,6 +386,137 @@ pub struct Private {
     pub registries: Vec<String>,
 }
 
+/// Configures the persistent, on-disk cache
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Cache {
+    /// The directory used to store cached data, defaults to
+    /// `$CARGO_HOME/cargo-about` if not specified
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// Disables the persistent cache entirely
+    #[serde(default)]
+    pub disabled: bool,
+    /// The number of seconds a definition retrieved from clearlydefined.io is
+    /// considered up to date before it is requested again
+    #[serde(default = "default_clearly_defined_ttl")]
+    pub clearly_defined_ttl: u64,
+}
+
+#[inline]
+fn default_clearly_defined_ttl() -> u64 {
+    // 1 week
+    7 * 24 * 60 * 60
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self {
+            path: None,
+            disabled: false,
+            clearly_defined_ttl: default_clearly_defined_ttl(),
+        }
+    }
+}
+
+/// The directories at the root of a crate that are not scanned for license
+/// files unless `default-excludes` is disabled
+pub const DEFAULT_SCAN_EXCLUDES: &[&str] = &["/tests/", "/benches/", "/examples/"];
+
+/// How a lint is reported
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintLevel {
+    /// The lint is not reported
+    Allow,
+    /// The lint is reported as a warning
+    Warn,
+    /// The lint is reported as an error
+    Deny,
+}
+
+impl Default for LintLevel {
+    fn default() -> Self {
+        Self::Warn
+    }
+}
+
+/// Determines which files in a crate are scanned for license information
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScanMode {
+    /// Only files that are likely to contain license information, eg.
+    /// `LICENSE` or `COPYING`, are scanned
+    Fast,
+    /// Every text file is scanned
+    Deep,
+}
+
+impl Default for ScanMode {
+    fn default() -> Self {
+        Self::Fast
+    }
+}
+
+/// Configures which files in a crate are scanned for license information
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Scan {
+    /// Glob patterns of crate relative paths, if specified, only files that
+    /// match at least one of them are scanned
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Glob patterns of crate relative paths that are not scanned
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Whether the [`DEFAULT_SCAN_EXCLUDES`] are applied, defaults to true
+    #[serde(default)]
+    pub default_excludes: Option<bool>,
+    /// Which files are scanned, defaults to [`ScanMode::Fast`]
+    #[serde(default)]
+    pub mode: Option<ScanMode>,
+}
+
+/// The format of a generated output
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// Renders one or more handlebars templates
+    Handlebars,
+    /// A stable, versioned JSON document
+    Json,
+    /// An SPDX 2.3 document in the JSON serialization
+    SpdxJson,
+    /// An SPDX 2.3 document in the tag-value serialization
+    SpdxTag,
+    /// A `CycloneDX` 1.5 bom in the JSON serialization
+    CyclonedxJson,
+    /// A `CycloneDX` 1.5 bom in the XML serialization
+    CyclonedxXml,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self::Handlebars
+    }
+}
+
+/// An output that is rendered from the gathered license information
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Output {
+    /// The format of the output, defaults to [`OutputFormat::Handlebars`]
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// The template(s) or template directory to render, relative to the
+    /// config, required for the handlebars format
+    pub template: Option<PathBuf>,
+    /// The name of the template to render if `template` is a directory
+    pub name: Option<String>,
+    /// The path the output is written to, relative to the config
+    pub path: PathBuf,
+}
+
 #[derive(Deserialize, Debug, Default)]
 #[serde(rename_all = "kebab-case")]
 pub struct Config {
@@ -202,6 +531,23 @@ pub struct Config {
     /// information and relies purely on local file scanning and clarifications
     #[serde(default)]
     pub no_clearly_defined: bool,
+    /// Configures the persistent cache used to avoid retrieving the same
+    /// data on every run
+    #[serde(default)]
+    pub cache: Cache,
+    /// Configures which files are scanned for license information for every
+    /// crate
+    #[serde(default)]
+    pub scan: Scan,
+    /// How license headers in a crate's files that aren't part of the crate's
+    /// declared license are reported. Source files are only scanned for
+    /// headers with [`ScanMode::Deep`]
+    #[serde(default)]
+    pub header_mismatch: LintLevel,
+    /// Also extracts copyright statements from the headers of files that
+    /// license headers were detected in, not just from license files
+    #[serde(default)]
+    pub header_copyrights: bool,
     /// Ignores any build dependencies in the graph
     #[serde(default)]
     pub ignore_build_dependencies: bool,
@@ -215,13 +561,183 @@ pub struct Config {
     /// The list of licenses we will use for all crates, in priority order
     #[serde(deserialize_with = "deserialize_licensee")]
     pub accepted: Vec<spdx::Licensee>,
+    /// The list of licenses that are never allowed, even if they are accepted,
+    /// unless there is an exception for a specific crate
+    #[serde(default, deserialize_with = "deserialize_licensee")]
+    pub denied: Vec<spdx::Licensee>,
+    /// Licenses that are allowed only for specific crates
+    #[serde(default)]
+    pub exceptions: Vec<Exception>,
     /// Some crates have extremely complicated licensing which requires tedious
     /// configuration to actually correctly identify. Rather than require every
     /// user of cargo-about to redo that same configuration if they happen to
     /// use those problematic crates, they can apply workarounds instead.
     #[serde(default)]
     pub workarounds: Vec<String>,
+    /// The outputs that are rendered by `generate` if an output isn't
+    /// specified on the command line
+    #[serde(default, rename = "output")]
+    pub outputs: Vec<Output>,
     /// Crate specific configuration
-    #[serde(flatten)]
-    pub crates: BTreeMap<String, KrateConfig>,
+    #[serde(flatten, deserialize_with = "deserialize_krate_configs")]
+    pub crates: Vec<KrateConfig>,
+}
+
+impl Config {
+    /// Gets every crate specific configuration that applies to the crate, in
+    /// the order they were declared
+    pub fn krate_configs<'c>(
+        &'c self,
+        krate: &'c crate::Krate,
+    ) -> impl Iterator<Item = &'c KrateConfig> + 'c {
+        self.crates.iter().filter(move |kc| kc.matches(krate))
+    }
+
+    /// Gets the glob patterns that determine which files in the crate are
+    /// scanned, in gitignore syntax, where excluded patterns are prefixed with
+    /// `!`. Later patterns take precedence over earlier ones, so crate
+    /// specific patterns take precedence over global ones.
+    pub fn scan_globs(&self, krate: &crate::Krate) -> Vec<String> {
+        let scans: Vec<_> = self.scans(krate).collect();
+
+        let default_excludes = scans
+            .iter()
+            .rev()
+            .find_map(|scan| scan.default_excludes)
+            .unwrap_or(true);
+
+        let mut globs = Vec::new();
+        if default_excludes {
+            globs.extend(
+                DEFAULT_SCAN_EXCLUDES
+                    .iter()
+                    .map(|glob| format!("!{}", glob)),
+            );
+        }
+
+        for scan in scans {
+            globs.extend(scan.include.iter().cloned());
+            globs.extend(scan.exclude.iter().map(|glob| format!("!{}", glob)));
+        }
+
+        globs
+    }
+
+    /// Gets the mode the crate is scanned with, the crate specific mode takes
+    /// precedence over the global mode
+    pub fn scan_mode(&self, krate: &crate::Krate) -> ScanMode {
+        self.scans(krate)
+            .filter_map(|scan| scan.mode)
+            .last()
+            .unwrap_or_default()
+    }
+
+    /// The global scan configuration, followed by the scan configuration of
+    /// every crate configuration that applies to the crate
+    fn scans<'c>(&'c self, krate: &'c crate::Krate) -> impl Iterator<Item = &'c Scan> + 'c {
+        std::iter::once(&self.scan).chain(self.krate_configs(krate).map(|kc| &kc.scan))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn version_scoped_crates() {
+        let cfg: Config = toml::from_str(
+            r#"
+accepted = ["MIT"]
+
+[ring]
+accepted = ["ISC"]
+
+["ring@0.16"]
+accepted = ["OpenSSL"]
+
+[[webpki]]
+version = "<0.22"
+accepted = ["ISC"]
+
+[[webpki]]
+version = ">=0.22"
+accepted = ["BSD-3-Clause"]
+"#,
+        )
+        .unwrap();
+
+        let scoped: Vec<_> = cfg
+            .crates
+            .iter()
+            .map(|kc| {
+                (
+                    kc.name.as_str(),
+                    kc.version.as_ref().map(|vr| vr.to_string()),
+                    kc.accepted[0].to_string(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            scoped,
+            vec![
+                ("ring", None, "ISC".to_owned()),
+                ("ring", Some("^0.16".to_owned()), "OpenSSL".to_owned()),
+                ("webpki", Some("<0.22".to_owned()), "ISC".to_owned()),
+                (
+                    "webpki",
+                    Some(">=0.22".to_owned()),
+                    "BSD-3-Clause".to_owned()
+                ),
+            ]
+        );
+
+        assert!(toml::from_str::<Config>(
+            r#"
+accepted = ["MIT"]
+
+["ring@0.16"]
+version = "0.16"
+"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn keeps_declaration_order() {
+        let cfg: Config = toml::from_str(
+            r#"
+accepted = ["MIT"]
+
+[zlib]
+accepted = ["Zlib"]
+
+["ring@0.16"]
+accepted = ["OpenSSL"]
+
+[ring]
+accepted = ["ISC"]
+
+[[aho-corasick]]
+accepted = ["Unlicense"]
+"#,
+        )
+        .unwrap();
+
+        let declared: Vec<_> = cfg
+            .crates
+            .iter()
+            .map(|kc| (kc.name.as_str(), kc.accepted[0].to_string()))
+            .collect();
+
+        assert_eq!(
+            declared,
+            vec![
+                ("zlib", "Zlib".to_owned()),
+                ("ring", "OpenSSL".to_owned()),
+                ("ring", "ISC".to_owned()),
+                ("aho-corasick", "Unlicense".to_owned()),
+            ]
+        );
+    }
 }
@@ -0,0 +1,369 @@
+//! Extraction of copyright statements, eg. `Copyright (c) 2019 Foo`, from
+//! license texts and source file headers
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::{collections::BTreeSet, fmt};
+
+/// A copyright statement
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Copyright {
+    /// The years the copyright applies to
+    pub years: BTreeSet<u16>,
+    /// The holder(s) of the copyright
+    pub holder: String,
+}
+
+impl Copyright {
+    /// The years, with consecutive years collapsed into ranges, eg.
+    /// `2015-2017, 2019`
+    pub fn years(&self) -> String {
+        let mut ranges: Vec<(u16, u16)> = Vec::new();
+        for &year in &self.years {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == year => *end = year,
+                _ => ranges.push((year, year)),
+            }
+        }
+
+        ranges
+            .into_iter()
+            .map(|(start, end)| {
+                if start == end {
+                    start.to_string()
+                } else {
+                    format!("{}-{}", start, end)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl fmt::Display for Copyright {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.years.is_empty() {
+            write!(f, "Copyright (c) {}", self.holder)
+        } else {
+            write!(f, "Copyright (c) {} {}", self.years(), self.holder)
+        }
+    }
+}
+
+impl Serialize for Copyright {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Copyright", 3)?;
+        s.serialize_field("years", &self.years())?;
+        s.serialize_field("holder", &self.holder)?;
+        s.serialize_field("statement", &self.to_string())?;
+        s.end()
+    }
+}
+
+/// Merges copyrights for the same holder, in the order the holders first
+/// appear
+pub fn merge(copyrights: impl IntoIterator<Item = Copyright>) -> Vec<Copyright> {
+    let mut merged: Vec<Copyright> = Vec::new();
+    for copyright in copyrights {
+        match merged.iter_mut().find(|c| c.holder == copyright.holder) {
+            Some(existing) => existing.years.extend(copyright.years),
+            None => merged.push(copyright),
+        }
+    }
+    merged
+}
+
+/// Extracts every copyright statement from the text
+pub fn extract(text: &str) -> Vec<Copyright> {
+    let lines: Vec<_> = text.lines().map(strip_comment).collect();
+
+    merge(lines.iter().enumerate().filter_map(|(i, line)| {
+        let (years, holder) = parse_statement(line)?;
+
+        // The holder is sometimes on the line following the years
+        let holder = if holder.is_empty() && !years.is_empty() {
+            lines
+                .get(i + 1)
+                .filter(|next| strip_markers(next).1 == 0)
+                .map_or("", |next| *next)
+        } else {
+            holder
+        };
+
+        let (holder, trailing) = split_trailing_years(holder);
+        let holder = normalize_holder(holder)?;
+
+        Some(Copyright {
+            years: years.into_iter().chain(trailing).collect(),
+            holder,
+        })
+    }))
+}
+
+/// Words that, when they directly follow `Copyright`, indicate that the line
+/// is prose about copyright rather than a copyright statement
+const STOP_WORDS: &[&str] = &[
+    "and",
+    "claim",
+    "holder",
+    "holders",
+    "law",
+    "license",
+    "licence",
+    "notice",
+    "notices",
+    "owner",
+    "owners",
+    "protection",
+    "statement",
+    "statements",
+];
+
+/// Strips leading comment markers from a source line
+fn strip_comment(line: &str) -> &str {
+    line.trim()
+        .trim_start_matches(|c: char| matches!(c, '/' | '*' | '#' | ';' | '!' | '-' | '%'))
+        .trim()
+}
+
+fn strip_prefix_ignore_case<'s>(s: &'s str, prefix: &str) -> Option<&'s str> {
+    s.get(..prefix.len())
+        .filter(|start| start.eq_ignore_ascii_case(prefix))
+        .map(|_| &s[prefix.len()..])
+}
+
+/// Strips any number of `Copyright`, `(c)`, and `©` markers from the start of
+/// the line, returning the remainder, the number of markers, and whether any
+/// of them were something other than `(c)`
+fn strip_markers(mut s: &str) -> (&str, usize, bool) {
+    let mut count = 0;
+    let mut explicit = false;
+
+    loop {
+        s = s.trim_start();
+
+        if let Some(rest) = strip_prefix_ignore_case(s, "copyright") {
+            // Avoid matching eg. `copyrighted` or `copyrights`
+            if rest.starts_with(char::is_alphanumeric) {
+                break;
+            }
+
+            s = rest.strip_prefix(':').unwrap_or(rest);
+            explicit = true;
+        } else if let Some(rest) = s.strip_prefix('©') {
+            s = rest;
+            explicit = true;
+        } else if let Some(rest) = strip_prefix_ignore_case(s, "&copy;") {
+            s = rest;
+            explicit = true;
+        } else if let Some(rest) = strip_prefix_ignore_case(s, "(c)") {
+            s = rest;
+        } else {
+            break;
+        }
+
+        count += 1;
+    }
+
+    (s, count, explicit)
+}
+
+/// Parses a year, only accepting those that are plausibly part of a
+/// copyright statement
+fn parse_year(s: &str) -> Option<(u16, &str)> {
+    let digits = s.bytes().take_while(u8::is_ascii_digit).count();
+    if digits != 4 || s[digits..].starts_with(char::is_alphanumeric) {
+        return None;
+    }
+
+    let year: u16 = s[..digits].parse().ok()?;
+    (1950..=2100).contains(&year).then(|| (year, &s[digits..]))
+}
+
+/// Parses a list of years and year ranges, eg. `2015-2017, 2019`, from the
+/// start of the string, returning the years and the remainder
+fn parse_years(mut s: &str) -> (BTreeSet<u16>, &str) {
+    let mut years = BTreeSet::new();
+
+    while let Some((year, rest)) = parse_year(s.trim_start()) {
+        years.insert(year);
+        s = rest;
+
+        let after = s.trim_start();
+        let range_end = after
+            .strip_prefix('-')
+            .or_else(|| after.strip_prefix('–'))
+            .and_then(|after| parse_year(after.trim_start()));
+
+        if let Some((end, rest)) = range_end {
+            years.extend(year..=end);
+            s = rest;
+        }
+
+        match s.trim_start().strip_prefix(',') {
+            Some(rest) => s = rest,
+            None => {
+                // Allow years that are only separated by whitespace, but not
+                // anything else that follows them
+                if parse_year(s.trim_start()).is_none() {
+                    break;
+                }
+            }
+        }
+    }
+
+    (years, s)
+}
+
+/// Parses a line as a copyright statement, returning the years and the
+/// remaining unnormalized holder
+fn parse_statement(line: &str) -> Option<(BTreeSet<u16>, &str)> {
+    let (rest, count, explicit) = strip_markers(line);
+    if count == 0 {
+        return None;
+    }
+
+    let (years, holder) = parse_years(rest);
+
+    if years.is_empty() {
+        // A lone `(c)` is more likely to be an item in a list, eg. the
+        // conditions in Apache-2.0, so years are required to disambiguate
+        if !explicit {
+            return None;
+        }
+
+        let first = holder.split_whitespace().next()?;
+        if first.starts_with(char::is_lowercase)
+            || STOP_WORDS.contains(&first.to_lowercase().as_str())
+        {
+            return None;
+        }
+    }
+
+    let holder = holder.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    let holder = strip_prefix_ignore_case(holder, "by ").unwrap_or(holder);
+
+    Some((years, holder))
+}
+
+/// Splits years that follow the holder, eg. `Foo, 2019`, from the holder
+fn split_trailing_years(holder: &str) -> (&str, BTreeSet<u16>) {
+    let trimmed = holder.trim_end_matches(|c: char| c == '.' || c.is_whitespace());
+
+    for (i, c) in trimmed.char_indices() {
+        if c != ',' && !c.is_whitespace() {
+            continue;
+        }
+
+        let (years, rest) = parse_years(&trimmed[i + c.len_utf8()..]);
+        if !years.is_empty() && rest.trim().is_empty() {
+            return (&trimmed[..i], years);
+        }
+    }
+
+    (holder, BTreeSet::new())
+}
+
+/// Normalizes the holder, returning `None` if it isn't a plausible holder,
+/// eg. if it is a placeholder in the canonical text of a license
+fn normalize_holder(holder: &str) -> Option<String> {
+    if holder.starts_with(|c| matches!(c, '<' | '[' | '{')) {
+        return None;
+    }
+
+    let lower = holder.to_ascii_lowercase();
+    let holder = match lower.find("all rights reserved") {
+        Some(i) => &holder[..i],
+        None => holder,
+    };
+
+    // Drop parentheticals that continue on the following line
+    let holder = match holder.rfind('(') {
+        Some(i) if !holder[i..].contains(')') => &holder[..i],
+        _ => holder,
+    };
+
+    let holder = holder
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(|c| matches!(c, '.' | ',' | ';' | ':'))
+        .to_owned();
+
+    holder.contains(char::is_alphabetic).then(|| holder)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{extract, merge, Copyright};
+
+    fn statements(text: &str) -> Vec<String> {
+        extract(text).iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn extracts_statements() {
+        assert_eq!(
+            statements("Copyright (c) 2019 Foo Bar"),
+            ["Copyright (c) 2019 Foo Bar"]
+        );
+        assert_eq!(
+            statements("// Copyright © 2015-2017, 2019 The Foo Developers."),
+            ["Copyright (c) 2015-2017, 2019 The Foo Developers"]
+        );
+        assert_eq!(
+            statements("# Copyright 2014 2016 by Foo <foo@bar.com>. All rights reserved."),
+            ["Copyright (c) 2014, 2016 Foo <foo@bar.com>"]
+        );
+        assert_eq!(
+            statements(" * (C) 2020 Foo, Inc."),
+            ["Copyright (c) 2020 Foo, Inc"]
+        );
+        assert_eq!(
+            statements("Copyright The Rust Project Developers, 2018"),
+            ["Copyright (c) 2018 The Rust Project Developers"]
+        );
+        assert_eq!(
+            statements("Copyright (c) 2016\n    Foo Bar\n"),
+            ["Copyright (c) 2016 Foo Bar"]
+        );
+        assert_eq!(
+            statements("Copyright (c) 2015-2016 the foo authors (see\nhttps://foo.org/AUTHORS)."),
+            ["Copyright (c) 2015-2016 the foo authors"]
+        );
+    }
+
+    #[test]
+    fn ignores_prose_and_placeholders() {
+        assert!(statements("Copyright (c) <year> <copyright holders>").is_empty());
+        assert!(statements("Copyright [yyyy] [name of copyright owner]").is_empty());
+        assert!(statements("copyright notice and this permission notice").is_empty());
+        assert!(statements("Copyright license to reproduce, prepare Derivative").is_empty());
+        assert!(statements("(c) You must retain, in the Source form").is_empty());
+        assert!(statements("Copyrighted material").is_empty());
+        assert!(statements("Copyright (c) 2019").is_empty());
+    }
+
+    #[test]
+    fn merges_holders() {
+        let merged = merge(
+            extract("Copyright 2015 Foo\nCopyright 2016 Bar\nCopyright (c) 2016-2017 Foo")
+                .into_iter()
+                .chain(extract("Copyright 2019 Bar.")),
+        );
+
+        assert_eq!(
+            merged,
+            [
+                Copyright {
+                    years: [2015, 2016, 2017].into_iter().collect(),
+                    holder: "Foo".to_owned(),
+                },
+                Copyright {
+                    years: [2016, 2019].into_iter().collect(),
+                    holder: "Bar".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(merged[1].years(), "2016, 2019");
+    }
+}
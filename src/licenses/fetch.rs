@@ -1,10 +1,93 @@
-use super::{config, Krate};
+use super::{cache::Cache, Krate};
 use anyhow::Context as _;
 use krates::Utf8Path as Path;
 use reqwest::blocking::Client;
-use std::{io::Read, sync::Arc};
+use std::{fmt, io::Read, sync::Arc};
 use url::Url;
 
+/// Determines if cargo-about is allowed to access the network
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NetworkMode {
+    /// Remote data is retrieved as needed
+    Online,
+    /// Remote data is never retrieved, only data that is already cached is used
+    Offline,
+    /// The same as `Offline`, except it is an error if any remote data would
+    /// have needed to be retrieved
+    Frozen,
+}
+
+impl Default for NetworkMode {
+    fn default() -> Self {
+        Self::Online
+    }
+}
+
+/// A remote retrieval that was denied because the network is not allowed
+pub struct DeniedFetch {
+    /// The crate the data was being retrieved for
+    pub krate: String,
+    /// A description of what was being retrieved
+    pub what: String,
+}
+
+impl fmt::Display for DeniedFetch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} for crate '{}'", self.what, self.krate)
+    }
+}
+
+/// The single gate that all network access goes through, so that it can be
+/// disallowed, and any attempts at accessing it recorded
+#[derive(Clone, Default)]
+pub struct Network {
+    mode: NetworkMode,
+    denied: Arc<parking_lot::Mutex<Vec<DeniedFetch>>>,
+}
+
+impl Network {
+    pub fn new(mode: NetworkMode) -> Self {
+        Self {
+            mode,
+            denied: Arc::default(),
+        }
+    }
+
+    #[inline]
+    pub fn mode(&self) -> NetworkMode {
+        self.mode
+    }
+
+    #[inline]
+    pub fn is_online(&self) -> bool {
+        self.mode == NetworkMode::Online
+    }
+
+    /// Checks if the specified data can be retrieved from a remote source,
+    /// recording the attempt if it can't
+    pub fn allow(&self, krate: &Krate, what: impl fmt::Display) -> anyhow::Result<()> {
+        if self.is_online() {
+            return Ok(());
+        }
+
+        let denied = DeniedFetch {
+            krate: krate.to_string(),
+            what: what.to_string(),
+        };
+
+        let err = anyhow::anyhow!("network access is disabled, unable to retrieve {}", denied);
+        log::debug!("{:#}", err);
+        self.denied.lock().push(denied);
+
+        Err(err)
+    }
+
+    /// Takes all of the retrievals that were denied
+    pub fn take_denied(&self) -> Vec<DeniedFetch> {
+        std::mem::take(&mut *self.denied.lock())
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 enum GitHostFlavor {
     Github,
@@ -113,16 +196,34 @@ pub struct VcsInfo {
 #[derive(Clone, Default)]
 pub struct GitCache {
     cache: Arc<parking_lot::RwLock<std::collections::HashMap<u64, Arc<String>>>>,
+    /// The workspace roots of git and path crates, keyed by their manifest path
+    roots: Arc<parking_lot::RwLock<std::collections::HashMap<super::PathBuf, super::PathBuf>>>,
+    disk: Option<Cache>,
+    network: Network,
     http_client: Client,
 }
 
 impl GitCache {
-    #[allow(clippy::unused_self)]
-    fn retrieve_local(
-        &self,
-        krate: &Krate,
-        file: &config::ClarificationFile,
-    ) -> anyhow::Result<String> {
+    /// Persists retrieved files to the specified cache so that they don't
+    /// need to be retrieved again in subsequent runs
+    pub fn with_cache(mut self, cache: Option<Cache>) -> Self {
+        self.disk = cache;
+        self
+    }
+
+    /// Sets the gate used to determine if files can be retrieved remotely
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Locates the root of the workspace the crate is a member of, spawning
+    /// cargo only the first time it is needed for each crate
+    fn workspace_root(&self, krate: &Krate) -> anyhow::Result<super::PathBuf> {
+        if let Some(root) = self.roots.read().get(&krate.manifest_path) {
+            return Ok(root.clone());
+        }
+
         // The only reason this kind of clarification should be used is when the
         // file in question is not part of the published package, which is
         // almost always because it is in a parent directory, so we request the
@@ -130,7 +231,7 @@ impl GitCache {
         // in mind, though this might fail in more complicated scenarios like if
         // there are multiple workspaces in a single repository
         let mut cmd = std::process::Command::new("cargo");
-        cmd.args(&[
+        cmd.args([
             "locate-project",
             "--workspace",
             "--manifest-path",
@@ -163,7 +264,16 @@ impl GitCache {
                 )
             })?;
 
-        let license_path = root.parent().unwrap().join(&file.path);
+        let root = root.parent().unwrap().to_owned();
+        self.roots
+            .write()
+            .insert(krate.manifest_path.clone(), root.clone());
+
+        Ok(root)
+    }
+
+    fn retrieve_local(&self, krate: &Krate, path: &Path) -> anyhow::Result<String> {
+        let license_path = self.workspace_root(krate)?.join(path);
 
         let contents = std::fs::read_to_string(&license_path)
             .with_context(|| format!("unable to read path '{}'", license_path))?;
@@ -208,7 +318,7 @@ impl GitCache {
     pub(crate) fn retrieve(
         &self,
         krate: &Krate,
-        file: &config::ClarificationFile,
+        path: &Path,
         commit_override: &Option<String>,
     ) -> anyhow::Result<Arc<String>> {
         match &krate.source {
@@ -219,7 +329,7 @@ impl GitCache {
                 // may not include the file we are looking for, so we need to
                 // fetch it with a remote call
                 if src.repr.starts_with("git+") {
-                    self.retrieve_local(krate, file).map(Arc::new)
+                    self.retrieve_local(krate, path).map(Arc::new)
                 } else if src.repr.starts_with("registry+") {
                     let repo = krate.repository.as_deref().with_context(|| {
                         format!(
@@ -250,7 +360,7 @@ impl GitCache {
 
                         hasher.write(repo.as_bytes());
                         hasher.write(sha1.as_bytes());
-                        hasher.write(file.path.as_str().as_bytes());
+                        hasher.write(path.as_str().as_bytes());
 
                         hasher.finish()
                     };
@@ -259,7 +369,31 @@ impl GitCache {
                         return Ok(text.clone());
                     }
 
-                    let contents = Arc::new(self.retrieve_remote(repo, &sha1, &file.path)?);
+                    let cached = self
+                        .disk
+                        .as_ref()
+                        .and_then(|disk| disk.git_file(repo, &sha1, path));
+
+                    let contents = match cached {
+                        Some(contents) => {
+                            log::debug!("using cached '{}' for crate '{}'", path, krate);
+                            Arc::new(contents)
+                        }
+                        None => {
+                            self.network.allow(
+                                krate,
+                                format_args!("'{}' from repo '{}' @ {}", path, repo, sha1),
+                            )?;
+
+                            let contents = self.retrieve_remote(repo, &sha1, path)?;
+
+                            if let Some(disk) = &self.disk {
+                                disk.store_git_file(repo, &sha1, path, &contents);
+                            }
+
+                            Arc::new(contents)
+                        }
+                    };
 
                     self.cache.write().insert(hash, contents.clone());
 
@@ -275,7 +409,7 @@ impl GitCache {
             None => {
                 // No source means this is a path dependency, so we just treat it
                 // as a regular path from the crate root
-                self.retrieve_local(krate, file).map(Arc::new)
+                self.retrieve_local(krate, path).map(Arc::new)
             }
         }
     }
@@ -1,9 +1,9 @@
 use crate::{
-    licenses::{config, KrateLicense, LicenseInfo},
+    licenses::{config, KrateLicense, LicenseFile, LicenseFileKind, LicenseInfo},
     Krate,
 };
 use spdx::{Expression, LicenseReq, Licensee};
-use std::fmt;
+use std::{collections::BTreeMap, fmt};
 type Label = codespan_reporting::diagnostic::Label<codespan::FileId>;
 use codespan_reporting::diagnostic::LabelStyle;
 
@@ -11,53 +11,126 @@ pub use codespan_reporting::diagnostic::Severity;
 pub type Diagnostic = codespan_reporting::diagnostic::Diagnostic<codespan::FileId>;
 pub type Files = codespan::Files<String>;
 
-struct Accepted<'acc> {
-    global: &'acc [Licensee],
-    krate: Option<&'acc [Licensee]>,
+/// The reason a license requirement was rejected
+enum Rejection<'p> {
+    /// The license matched an entry in the `denied` list
+    Denied(&'p Licensee),
+    /// The license is not in any `accepted` list, nor allowed by an exception
+    NotAccepted,
 }
 
-impl<'acc> Accepted<'acc> {
+/// The licensing policy that applies to a single crate
+struct Policy<'p> {
+    global: &'p [Licensee],
+    /// The additional accepted licenses from every crate configuration that
+    /// applies to the crate
+    krate: Vec<&'p Licensee>,
+    denied: &'p [Licensee],
+    /// The exceptions that apply to the crate, and their index in the config
+    exceptions: Vec<(usize, &'p config::Exception)>,
+}
+
+impl<'p> Policy<'p> {
+    fn new(cfg: &'p config::Config, krate: &'p Krate) -> Self {
+        Self {
+            global: &cfg.accepted,
+            krate: cfg
+                .krate_configs(krate)
+                .flat_map(|kcfg| kcfg.accepted.iter())
+                .collect(),
+            denied: &cfg.denied,
+            exceptions: cfg
+                .exceptions
+                .iter()
+                .enumerate()
+                .filter(|(_, exc)| exc.matches(krate))
+                .collect(),
+        }
+    }
+
+    /// Checks the requirement against the policy. Exceptions take precedence
+    /// over denied licenses, which take precedence over accepted licenses.
+    fn evaluate(&self, req: &spdx::LicenseReq) -> Result<(), Rejection<'p>> {
+        if self
+            .exceptions
+            .iter()
+            .any(|(_, exc)| exc.allow.iter().any(|lic| lic.satisfies(req)))
+        {
+            return Ok(());
+        }
+
+        if let Some(denied) = self.denied.iter().find(|lic| lic.satisfies(req)) {
+            return Err(Rejection::Denied(denied));
+        }
+
+        if self
+            .global
+            .iter()
+            .chain(self.krate.iter().copied())
+            .any(|lic| lic.satisfies(req))
+        {
+            Ok(())
+        } else {
+            Err(Rejection::NotAccepted)
+        }
+    }
+
     #[inline]
     fn satisfies(&self, req: &spdx::LicenseReq) -> bool {
-        self.iter().any(|licensee| licensee.satisfies(req))
+        self.evaluate(req).is_ok()
     }
 
+    /// The licenses that are allowed by the policy, in priority order
     #[inline]
-    fn iter(&'acc self) -> impl Iterator<Item = &'acc Licensee> {
+    fn iter(&'p self) -> impl Iterator<Item = &'p Licensee> {
         self.global
             .iter()
-            .chain(self.krate.iter().flat_map(|o| o.iter()))
+            .chain(self.krate.iter().copied())
+            .chain(self.exceptions.iter().flat_map(|(_, exc)| exc.allow.iter()))
+            // A license can be both accepted and denied, in which case it is denied
+            .filter(move |lic| self.satisfies(&(*lic).clone().into_req()))
     }
 }
 
-impl<'acc> fmt::Display for Accepted<'acc> {
+impl<'p> fmt::Display for Policy<'p> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "global: [")?;
-        for (id, val) in self.global.iter().enumerate() {
-            write!(f, "{}", val)?;
-            if id + 1 < self.global.len() {
-                write!(f, ", ")?;
-            }
-        }
-        write!(f, "]")?;
-
-        if let Some(krate) = self.krate {
-            write!(f, "\ncrate: [")?;
-            for (id, val) in krate.iter().enumerate() {
+        fn list<'l>(
+            f: &mut fmt::Formatter<'_>,
+            name: &str,
+            lics: impl ExactSizeIterator<Item = &'l Licensee>,
+        ) -> fmt::Result {
+            write!(f, "{}: [", name)?;
+            let len = lics.len();
+            for (id, val) in lics.enumerate() {
                 write!(f, "{}", val)?;
-                if id + 1 < krate.len() {
+                if id + 1 < len {
                     write!(f, ", ")?;
                 }
             }
-            write!(f, "]")?;
+            write!(f, "]")
+        }
+
+        list(f, "global", self.global.iter())?;
+
+        if !self.krate.is_empty() {
+            writeln!(f)?;
+            list(f, "crate", self.krate.iter().copied())?;
+        }
+
+        for (i, exc) in &self.exceptions {
+            writeln!(f)?;
+            list(f, &format!("exceptions[{}]", i), exc.allow.iter())?;
         }
 
         Ok(())
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Resolved {
+    /// The effective license expression that was resolved, which is
+    /// synthesized from the crate's license files if it didn't declare one
+    pub expression: Option<Expression>,
     /// The minimum license requirements that are required
     pub licenses: Vec<LicenseReq>,
     /// Diagnostics emitted during the course of the license resolution, may
@@ -91,16 +164,52 @@ fn synthesize_manifest(
     let serialized = doc.to_string();
 
     let offset = serialized
-        .find(&expression.as_ref())
+        .find(expression.as_ref())
         .expect("we literally just serialized this");
     (serialized, offset)
 }
 
+/// Gets the license headers that aren't part of the crate's license
+/// expression, and the files they were found in
+fn header_mismatches<'lf>(
+    expr: &Expression,
+    license_files: &'lf [LicenseFile],
+) -> BTreeMap<String, Vec<&'lf LicenseFile>> {
+    let is_declared = |item: &spdx::LicenseItem| {
+        expr.requirements().any(|er| match (&er.req.license, item) {
+            // The same license, regardless of `+`
+            (spdx::LicenseItem::Spdx { id: a, .. }, spdx::LicenseItem::Spdx { id: b, .. }) => {
+                a == b
+            }
+            (a, b) => a == b,
+        })
+    };
+
+    let mut mismatches = BTreeMap::<_, Vec<_>>::new();
+    for lf in license_files {
+        if !matches!(lf.kind, LicenseFileKind::Header) {
+            continue;
+        }
+
+        if !lf
+            .license_expr
+            .requirements()
+            .all(|er| is_declared(&er.req.license))
+        {
+            mismatches
+                .entry(lf.license_expr.to_string())
+                .or_default()
+                .push(lf);
+        }
+    }
+
+    mismatches
+}
+
 /// Find the minimal set of required licenses for each crate.
 pub fn resolve(
     licenses: &[KrateLicense<'_>],
-    accepted: &[Licensee],
-    krate_cfg: &std::collections::BTreeMap<String, config::KrateConfig>,
+    cfg: &config::Config,
 ) -> (Files, Vec<Option<Resolved>>) {
     let mut files = codespan::Files::new();
 
@@ -108,6 +217,7 @@ pub fn resolve(
         .iter()
         .map(|kl| {
             let mut resolved = Resolved {
+                expression: None,
                 licenses: Vec::new(),
                 diagnostics: Vec::new(),
             };
@@ -135,7 +245,17 @@ pub fn resolve(
                     let mut unique_exprs = Vec::new();
 
                     if kl.license_files.is_empty() {
-                        log::warn!("unable to synthesize license expression for '{}': no `license` specified, and no license files were found", kl.krate);
+                        resolved.diagnostics.push(
+                            Diagnostic::new(Severity::Warning)
+                                .with_message(format!(
+                                    "unable to synthesize license expression for '{}'",
+                                    kl.krate
+                                ))
+                                .with_notes(vec![
+                                    "no `license` specified, and no license files were found"
+                                        .to_owned(),
+                                ]),
+                        );
                         return Some(resolved);
                     }
 
@@ -184,6 +304,8 @@ pub fn resolve(
                 }
             };
 
+            resolved.expression = Some(expr.as_ref().clone());
+
             let expr_offset =
                 if let (LicenseInfo::Expr(expr), Some(manifest)) = (&kl.lic_info, &manifest) {
                     manifest.find(expr.as_ref())
@@ -214,45 +336,100 @@ pub fn resolve(
                 _ => synthesize_manifest(kl.krate, None, &expr),
             };
 
-            // Retrieve additional crate specific licenses
-            let accepted = match krate_cfg.get(&kl.krate.name) {
-                Some(kcfg) => {
-                    if kcfg.accepted.is_empty() {
-                        Accepted {
-                            global: accepted,
-                            krate: None,
-                        }
-                    } else {
-                        Accepted {
-                            global: accepted,
-                            krate: Some(&kcfg.accepted),
-                        }
-                    }
-                }
-                None => Accepted {
-                    global: accepted,
-                    krate: None,
-                },
-            };
+            let policy = Policy::new(cfg, kl.krate);
 
             let manifest_file_id = files.add(kl.krate.manifest_path.clone(), manifest);
 
+            // License headers in a crate's files, eg. from vendored code, can
+            // change the effective license of the crate
+            let severity = match cfg.header_mismatch {
+                config::LintLevel::Allow => None,
+                config::LintLevel::Warn => Some(Severity::Warning),
+                config::LintLevel::Deny => Some(Severity::Error),
+            };
+
+            if let (Some(severity), LicenseInfo::Expr(_)) = (severity, &kl.lic_info) {
+                let root = kl.krate.manifest_path.parent().unwrap();
+                let expr_span = expr_offset..expr_offset + AsRef::<str>::as_ref(&*expr).len();
+
+                for (header, lfs) in header_mismatches(&expr, &kl.license_files) {
+                    resolved.diagnostics.push(
+                        Diagnostic::new(severity)
+                            .with_message(format!(
+                                "license header '{}' found in '{}' is not part of its declared license",
+                                header, kl.krate
+                            ))
+                            .with_labels(vec![Label::new(
+                                LabelStyle::Primary,
+                                manifest_file_id,
+                                expr_span.clone(),
+                            )
+                            .with_message("declared license")])
+                            .with_notes(
+                                lfs.into_iter()
+                                    .map(|lf| {
+                                        format!(
+                                            "{} (confidence {:.2})",
+                                            lf.path.strip_prefix(root).unwrap_or(&lf.path),
+                                            lf.confidence
+                                        )
+                                    })
+                                    .collect(),
+                            ),
+                    );
+                }
+            }
+
             // Evaluates the expression against the accepted licenses to ensure it can
             // be satisfied according to the user's configuration
-            if let Err(failed) = expr.evaluate_with_failures(|req| accepted.satisfies(req)) {
+            if let Err(failed) = expr.evaluate_with_failures(|req| policy.satisfies(req)) {
+                let mut not_accepted = false;
+
+                let labels = failed
+                    .into_iter()
+                    .map(|fr| {
+                        let span = fr.span.start as usize + expr_offset
+                            ..fr.span.end as usize + expr_offset;
+
+                        let reason = match policy.evaluate(&fr.req) {
+                            Err(Rejection::Denied(denied)) => {
+                                format!("denied by `denied` entry '{}'", denied)
+                            }
+                            Err(Rejection::NotAccepted) | Ok(_) => {
+                                not_accepted = true;
+                                "not accepted".to_owned()
+                            }
+                        };
+
+                        Label::new(LabelStyle::Secondary, manifest_file_id, span)
+                            .with_message(reason)
+                    })
+                    .collect();
+
+                let mut notes = vec![format!(
+                    "license information determined by {}",
+                    kl.provenance
+                )];
+                if not_accepted {
+                    notes.push(format!("accepted licenses\n{}", policy));
+                }
+
+                // Exceptions for the crate that don't apply due to the version
+                // are most likely out of date
+                for (i, exc) in cfg.exceptions.iter().enumerate() {
+                    if exc.name == kl.krate.name && !exc.matches(kl.krate) {
+                        notes.push(format!(
+                            "exceptions[{}] for '{}' does not apply to version {}",
+                            i, exc.name, kl.krate.version
+                        ));
+                    }
+                }
+
                 resolved.diagnostics.push(
                     Diagnostic::new(Severity::Error)
                         .with_message("failed to satisfy license requirements")
-                        .with_labels(
-                            failed
-                                .into_iter()
-                                .map(|fr| {
-                                    let span = fr.span.start as usize + expr_offset
-                                        ..fr.span.end as usize + expr_offset;
-                                    Label::new(LabelStyle::Secondary, manifest_file_id, span)
-                                })
-                                .collect(),
-                        ),
+                        .with_labels(labels)
+                        .with_notes(notes),
                 );
 
                 return Some(resolved);
@@ -260,12 +437,19 @@ pub fn resolve(
 
             // Attempt to  find the minimal set of licenses needed to satisfy the
             // license requirements, in priority order
-            match expr.minimized_requirements(accepted.iter()) {
+            match expr.minimized_requirements(policy.iter()) {
                 Ok(min_reqs) => {
                     resolved.licenses = min_reqs;
                 }
                 Err(e) => {
-                    log::warn!("failed to minimize license requirements: {}", e);
+                    resolved.diagnostics.push(
+                        Diagnostic::new(Severity::Warning)
+                            .with_message(format!(
+                                "failed to minimize license requirements for '{}'",
+                                kl.krate
+                            ))
+                            .with_notes(vec![e.to_string()]),
+                    );
                 }
             }
 
@@ -275,3 +459,95 @@ pub fn resolve(
 
     (files, resolved)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn licensees(lics: &[&str]) -> Vec<Licensee> {
+        lics.iter().map(|l| Licensee::parse(l).unwrap()).collect()
+    }
+
+    fn req(lic: &str) -> spdx::LicenseReq {
+        Licensee::parse(lic).unwrap().into_req()
+    }
+
+    #[test]
+    fn header_mismatches() {
+        let file = |path: &str, license: &str, kind| LicenseFile {
+            license_expr: Expression::parse(license).unwrap(),
+            path: path.into(),
+            confidence: 0.9,
+            kind,
+            range: None,
+            provenance: crate::licenses::Provenance::FileSystem,
+        };
+
+        let files = vec![
+            file("LICENSE-MIT", "MIT", LicenseFileKind::Text(String::new())),
+            file(
+                "LICENSE-GPL",
+                "GPL-3.0",
+                LicenseFileKind::Text(String::new()),
+            ),
+            file("src/a.rs", "Apache-2.0", LicenseFileKind::Header),
+            file("src/b.rs", "MIT", LicenseFileKind::Header),
+            file("src/c.rs", "GPL-3.0", LicenseFileKind::Header),
+            file("src/d.rs", "GPL-3.0", LicenseFileKind::Header),
+        ];
+
+        let expr = Expression::parse("MIT OR Apache-2.0 AND GPL-2.0").unwrap();
+        let mismatches = super::header_mismatches(&expr, &files);
+
+        assert_eq!(
+            mismatches
+                .iter()
+                .map(|(lic, lfs)| (
+                    lic.as_str(),
+                    lfs.iter().map(|lf| lf.path.as_str()).collect()
+                ))
+                .collect::<Vec<(_, Vec<_>)>>(),
+            vec![("GPL-3.0", vec!["src/c.rs", "src/d.rs"])]
+        );
+    }
+
+    #[test]
+    fn exceptions_override_denied() {
+        let global = licensees(&["MIT", "AGPL-3.0"]);
+        let denied = licensees(&["AGPL-3.0", "GPL-2.0 WITH Classpath-exception-2.0"]);
+        let exception = config::Exception {
+            name: "krate".to_owned(),
+            version: None,
+            allow: licensees(&["GPL-2.0 WITH Classpath-exception-2.0"]),
+        };
+
+        let mut policy = Policy {
+            global: &global,
+            krate: Vec::new(),
+            denied: &denied,
+            exceptions: Vec::new(),
+        };
+
+        assert!(policy.satisfies(&req("MIT")));
+        assert!(matches!(
+            policy.evaluate(&req("AGPL-3.0")),
+            Err(Rejection::Denied(_))
+        ));
+        assert!(matches!(
+            policy.evaluate(&req("GPL-2.0 WITH Classpath-exception-2.0")),
+            Err(Rejection::Denied(_))
+        ));
+        assert!(matches!(
+            policy.evaluate(&req("Apache-2.0")),
+            Err(Rejection::NotAccepted)
+        ));
+
+        // Denied licenses are never used to satisfy requirements, even if accepted
+        assert_eq!(policy.iter().count(), 1);
+
+        policy.exceptions.push((0, &exception));
+        assert!(policy.satisfies(&req("GPL-2.0 WITH Classpath-exception-2.0")));
+        assert!(!policy.satisfies(&req("AGPL-3.0")));
+        assert_eq!(policy.iter().count(), 2);
+    }
+}
@@ -1,10 +1,78 @@
-use super::{LicenseFile, LicenseFileKind};
+use super::{config::ScanMode, LicenseFile, LicenseFileKind, Provenance};
+use anyhow::Context as _;
 use krates::{Utf8Path as Path, Utf8PathBuf as PathBuf};
 use rayon::prelude::*;
 
+/// Builds the overrides that filter the files that are scanned, from globs in
+/// the format returned by [`crate::licenses::config::Config::scan_globs`]
+fn overrides(root_dir: &Path, globs: &[String]) -> anyhow::Result<ignore::overrides::Override> {
+    let mut ob = ignore::overrides::OverrideBuilder::new(root_dir);
+    for glob in globs {
+        ob.add(glob)
+            .with_context(|| format!("invalid scan glob '{}'", glob))?;
+    }
+    Ok(ob.build()?)
+}
+
+/// The prefixes, in uppercase, of the names of files that are dedicated to
+/// license text
+const LICENSE_TEXT_PREFIXES: &[&str] = &["LICENSE", "LICENCE", "COPYING"];
+
+/// The prefixes, in uppercase, of the names of other files that likely contain
+/// license information
+const CANDIDATE_PREFIXES: &[&str] = &["NOTICE", "COPYRIGHT"];
+
+/// Whether the file is dedicated to license text, and can thus be split into
+/// multiple licenses if it doesn't match a single license
+fn is_license_text(path: &Path) -> bool {
+    let file_name = match path.file_name() {
+        Some(name) => name.to_uppercase(),
+        None => return false,
+    };
+
+    // The REUSE specification puts the license texts in a LICENSES directory
+    LICENSE_TEXT_PREFIXES
+        .iter()
+        .any(|prefix| file_name.starts_with(prefix))
+        || path
+            .parent()
+            .and_then(|parent| parent.file_name())
+            .map_or(false, |dir| dir.eq_ignore_ascii_case("LICENSES"))
+}
+
+/// Whether the file is likely to contain license information, and is thus
+/// scanned in [`ScanMode::Fast`]
+fn is_candidate(root_dir: &Path, path: &Path) -> bool {
+    if is_license_text(path) {
+        return true;
+    }
+
+    let file_name = match path.file_name() {
+        Some(name) => name.to_uppercase(),
+        None => return false,
+    };
+
+    if CANDIDATE_PREFIXES
+        .iter()
+        .any(|prefix| file_name.starts_with(prefix))
+        || file_name.ends_with(".LICENSE")
+    {
+        return true;
+    }
+
+    // READMEs often state the license of the crate, but only the top-level one
+    path.parent() == Some(root_dir) && file_name.starts_with("README")
+}
+
+/// Scans the files in the crate. License text files that don't match a single
+/// license are scanned again with the `segment` strategy, so that files that
+/// contain multiple licenses are split into a license file for each of them.
 pub(crate) fn scan_files(
     root_dir: &Path,
+    globs: &[String],
+    mode: ScanMode,
     strat: &askalono::ScanStrategy<'_>,
+    segment: &askalono::ScanStrategy<'_>,
     threshold: f32,
 ) -> anyhow::Result<Vec<LicenseFile>> {
     let types = {
@@ -18,6 +86,7 @@ pub(crate) fn scan_files(
         .standard_filters(true)
         .follow_links(true)
         .types(types)
+        .overrides(overrides(root_dir, globs)?)
         .build();
 
     let files: Vec<_> = walker.filter_map(|e| e.ok()).collect();
@@ -54,15 +123,69 @@ pub(crate) fn scan_files(
                 }
             };
 
+            if mode == ScanMode::Fast && !is_candidate(root_dir, &path) {
+                return None;
+            }
+
             let contents = read_file(&path)?;
 
-            check_is_license_file(path, contents, strat, threshold)
+            // Segmenting is expensive, so it's only done for files that are
+            // supposed to be license text, but don't match a single license
+            if is_license_text(&path) {
+                return match check_is_license_file(path.clone(), contents.clone(), strat, threshold)
+                {
+                    Some(lf) => Some(vec![lf]),
+                    None => segment_license_file(&path, &contents, strat, segment, threshold),
+                };
+            }
+
+            check_is_license_file(path, contents, strat, threshold).map(|lf| vec![lf])
         })
+        .flatten()
         .collect();
 
     Ok(license_files)
 }
 
+/// The names, in uppercase, of the files that are treated as NOTICE files
+const NOTICE_NAMES: &[&str] = &["NOTICE", "NOTICE.TXT", "NOTICE.MD"];
+
+pub(crate) fn is_notice(file_name: &str) -> bool {
+    NOTICE_NAMES.contains(&file_name.to_uppercase().as_str())
+}
+
+/// Finds the NOTICE files in the root of the crate
+pub(crate) fn find_notices(root_dir: &Path) -> Vec<super::Notice> {
+    let read_dir = match std::fs::read_dir(root_dir) {
+        Ok(rd) => rd,
+        Err(e) => {
+            log::error!("failed to read directory '{}': {}", root_dir, e);
+            return Vec::new();
+        }
+    };
+
+    let mut notices: Vec<_> = read_dir
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let file_name = entry.file_name().into_string().ok()?;
+
+            if !is_notice(&file_name) || !entry.file_type().ok()?.is_file() {
+                return None;
+            }
+
+            let text = read_file(&root_dir.join(&file_name))?;
+
+            Some(super::Notice {
+                path: PathBuf::from(file_name),
+                text,
+            })
+        })
+        .collect();
+
+    notices.sort_by(|a, b| a.path.cmp(&b.path));
+    notices
+}
+
 fn read_file(path: &Path) -> Option<String> {
     match std::fs::read_to_string(path) {
         Err(ref e) if e.kind() == std::io::ErrorKind::InvalidData => {
@@ -79,6 +202,233 @@ fn read_file(path: &Path) -> Option<String> {
     }
 }
 
+/// Gets the byte range of the 1-based, inclusive, range of lines in the
+/// text, defaulting to the first and last lines respectively
+pub(crate) fn line_range(
+    contents: &str,
+    start: Option<usize>,
+    end: Option<usize>,
+) -> anyhow::Result<std::ops::Range<usize>> {
+    let mut line_offsets = vec![0];
+    line_offsets.extend(
+        contents
+            .match_indices('\n')
+            .map(|(i, _)| i + 1)
+            .filter(|i| *i < contents.len()),
+    );
+
+    let line_count = line_offsets.len();
+    let start = start.unwrap_or(1);
+    let end = end.unwrap_or(line_count);
+
+    anyhow::ensure!(
+        start > 0 && start <= end,
+        "line range {}-{} is invalid",
+        start,
+        end
+    );
+    anyhow::ensure!(
+        end <= line_count,
+        "line {} is past the end of the file, which has {} lines",
+        end,
+        line_count
+    );
+
+    Ok(line_offsets[start - 1]..line_offsets.get(end).copied().unwrap_or(contents.len()))
+}
+
+/// The number of words a region of a segmented license file can have without
+/// matching a license before it's considered to contain one, since license
+/// files are often wrapped differently, or not at all
+const MAX_UNMATCHED_WORDS: usize = 50;
+
+fn word_count(lines: &[&str]) -> usize {
+    lines
+        .iter()
+        .map(|line| line.split_whitespace().count())
+        .sum()
+}
+
+/// Scans the specified lines of a file for the licenses they contain. The
+/// `TopDown` strategy can stop at a license and never find the ones that
+/// follow it, so any large region between the licenses that were found is
+/// scanned again on its own.
+fn find_segments<'a>(
+    path: &Path,
+    lines: &[&str],
+    region: std::ops::Range<usize>,
+    strat: &'a askalono::ScanStrategy<'a>,
+    segment: &'a askalono::ScanStrategy<'a>,
+    threshold: f32,
+    found: &mut Vec<askalono::ContainedResult<'a>>,
+) {
+    let text = askalono::TextData::new(&lines[region.clone()].concat());
+
+    // The region between licenses is often a single license, which the
+    // `TopDown` strategy can miss in a short region, as it only tries windows
+    // of lines that are a multiple of its step size. The whole file is only
+    // segmented if it didn't match a single license, so it isn't checked again.
+    if region.len() < lines.len() {
+        if let Ok(askalono::ScanResult {
+            score,
+            license: Some(license),
+            ..
+        }) = strat.scan(&text)
+        {
+            if score >= threshold {
+                found.push(askalono::ContainedResult {
+                    score,
+                    license,
+                    line_range: (region.start, region.end),
+                });
+                return;
+            }
+        }
+    }
+
+    let mut containing = match segment.scan(&text) {
+        Ok(result) => result.containing,
+        Err(e) => {
+            log::error!("failed to segment '{}': {}", path, e);
+            return;
+        }
+    };
+
+    // askalono counts the empty line after a trailing newline as a line
+    for contained in &mut containing {
+        let (start, end) = contained.line_range;
+        contained.line_range = (
+            (region.start + start).min(region.end),
+            (region.start + end).min(region.end),
+        );
+    }
+    containing.retain(|contained| contained.line_range.0 < contained.line_range.1);
+
+    if containing.is_empty() {
+        return;
+    }
+
+    containing.sort_by_key(|contained| contained.line_range.0);
+
+    let mut gaps = Vec::new();
+    let mut cursor = region.start;
+    for contained in &containing {
+        gaps.push(cursor..contained.line_range.0.max(cursor));
+        cursor = cursor.max(contained.line_range.1);
+    }
+    gaps.push(cursor..region.end);
+
+    found.append(&mut containing);
+
+    for gap in gaps {
+        if word_count(&lines[gap.clone()]) > MAX_UNMATCHED_WORDS {
+            find_segments(path, lines, gap, strat, segment, threshold, found);
+        }
+    }
+}
+
+/// Splits a file that contains multiple license texts, eg. a LICENSE file
+/// that concatenates the licenses of several vendored libraries, into a
+/// license file for each license. Returns `None` if no licenses were found.
+fn segment_license_file(
+    path: &Path,
+    contents: &str,
+    strat: &askalono::ScanStrategy<'_>,
+    segment: &askalono::ScanStrategy<'_>,
+    threshold: f32,
+) -> Option<Vec<LicenseFile>> {
+    let lines: Vec<_> = contents.split_inclusive('\n').collect();
+
+    let mut containing = Vec::new();
+    find_segments(
+        path,
+        &lines,
+        0..lines.len(),
+        strat,
+        segment,
+        threshold,
+        &mut containing,
+    );
+
+    if containing.is_empty() {
+        return None;
+    }
+
+    containing.sort_by_key(|contained| contained.line_range.0);
+
+    // Text that didn't match any license could still be one, eg. a license
+    // that was modified too much to be recognized, which would be silently
+    // left out of the license files for the crate
+    let mut cursor = 0;
+    for (start, end) in containing
+        .iter()
+        .map(|contained| contained.line_range)
+        .chain(std::iter::once((lines.len(), lines.len())))
+    {
+        if start > cursor && word_count(&lines[cursor..start]) > MAX_UNMATCHED_WORDS {
+            log::warn!(
+                "lines {}-{} of '{}' don't match any license, but may contain one",
+                cursor + 1,
+                start,
+                path
+            );
+        }
+
+        cursor = cursor.max(end);
+    }
+
+    let segments = containing
+        .into_iter()
+        .filter_map(|contained| {
+            // askalono's line ranges are 0-based and exclusive
+            let (start, end) = contained.line_range;
+            let range = match line_range(contents, Some(start + 1), Some(end)) {
+                Ok(range) => range,
+                Err(e) => {
+                    log::error!("invalid license segment in '{}': {:#}", path, e);
+                    return None;
+                }
+            };
+
+            let license_expr = match spdx::Expression::parse(contained.license.name) {
+                Ok(expr) => expr,
+                Err(err) => {
+                    log::error!(
+                        "failed to parse license '{}' into a valid expression: {}",
+                        contained.license.name,
+                        err
+                    );
+                    return None;
+                }
+            };
+
+            log::debug!(
+                "found '{}' in lines {}-{} of '{}'",
+                license_expr,
+                start + 1,
+                end,
+                path
+            );
+
+            Some(LicenseFile {
+                license_expr,
+                confidence: contained.score,
+                path: path.to_owned(),
+                kind: match contained.license.kind {
+                    askalono::LicenseType::Header => LicenseFileKind::Header,
+                    askalono::LicenseType::Original | askalono::LicenseType::Alternate => {
+                        LicenseFileKind::Text(contents[range.clone()].to_owned())
+                    }
+                },
+                range: Some(range),
+                provenance: Provenance::FileSystem,
+            })
+        })
+        .collect();
+
+    Some(segments)
+}
+
 pub(crate) fn check_is_license_file(
     path: PathBuf,
     contents: String,
@@ -107,6 +457,8 @@ pub(crate) fn check_is_license_file(
                 confidence: ided.confidence,
                 path,
                 kind: LicenseFileKind::Header,
+                range: None,
+                provenance: Provenance::FileSystem,
             })
         }
         ScanResult::Text(ided) => {
@@ -127,6 +479,8 @@ pub(crate) fn check_is_license_file(
                 confidence: ided.confidence,
                 path,
                 kind: LicenseFileKind::Text(contents),
+                range: None,
+                provenance: Provenance::FileSystem,
             })
         }
         ScanResult::UnknownId(id_str) => {
@@ -201,3 +555,182 @@ fn scan_text(contents: &str, strat: &askalono::ScanStrategy<'_>, threshold: f32)
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        is_candidate, is_license_text, is_notice, line_range, overrides, segment_license_file,
+    };
+    use krates::Utf8Path as Path;
+
+    #[test]
+    fn candidates() {
+        let root = Path::new("/krate");
+        let is = |path: &str| is_candidate(root, &root.join(path));
+
+        assert!(is("LICENSE"));
+        assert!(is("LICENSE-MIT"));
+        assert!(is("license.txt"));
+        assert!(is("LICENCE"));
+        assert!(is("COPYING"));
+        assert!(is("NOTICE.md"));
+        assert!(is("COPYRIGHT"));
+        assert!(is("vendor/zlib/LICENSE"));
+        assert!(is("src/data.bin.license"));
+        assert!(is("LICENSES/MIT.txt"));
+        assert!(is("README.md"));
+
+        assert!(!is("src/lib.rs"));
+        assert!(!is("vendor/zlib/README"));
+        assert!(!is("Cargo.toml"));
+
+        assert!(is_license_text(&root.join("COPYING")));
+        assert!(is_license_text(&root.join("LICENSES/MIT.txt")));
+        assert!(!is_license_text(&root.join("NOTICE")));
+        assert!(!is_license_text(&root.join("README.md")));
+    }
+
+    #[test]
+    fn notices() {
+        assert!(is_notice("NOTICE"));
+        assert!(is_notice("notice.txt"));
+        assert!(is_notice("Notice.md"));
+        assert!(!is_notice("NOTICE-THIRD-PARTY"));
+        assert!(!is_notice("LICENSE"));
+    }
+
+    #[test]
+    fn scan_overrides() {
+        let root = Path::new("/krate");
+        let globs: Vec<_> = ["!/tests/", "!/examples/", "vendor/**", "!vendor/**/*.c"]
+            .iter()
+            .map(|s| (*s).to_owned())
+            .collect();
+        let ov = overrides(root, &globs).unwrap();
+
+        let is_ignored = |path: &str, is_dir: bool| ov.matched(root.join(path), is_dir).is_ignore();
+
+        assert!(is_ignored("tests", true));
+        assert!(is_ignored("examples", true));
+        assert!(!is_ignored("src/tests", true));
+        assert!(!is_ignored("vendor/lib", true));
+        assert!(!is_ignored("vendor/lib/LICENSE", false));
+        assert!(is_ignored("vendor/lib/lib.c", false));
+        // Files not included are ignored if there are any includes
+        assert!(is_ignored("LICENSE", false));
+
+        assert!(overrides(root, &["[".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn line_ranges() {
+        let text = "one\ntwo\nthree\n";
+
+        assert_eq!(&text[line_range(text, None, None).unwrap()], text);
+        assert_eq!(
+            &text[line_range(text, Some(2), None).unwrap()],
+            "two\nthree\n"
+        );
+        assert_eq!(
+            &text[line_range(text, None, Some(2)).unwrap()],
+            "one\ntwo\n"
+        );
+        assert_eq!(&text[line_range(text, Some(2), Some(2)).unwrap()], "two\n");
+        assert_eq!(&text[line_range("one\ntwo", Some(2), None).unwrap()], "two");
+
+        assert!(line_range(text, Some(0), None).is_err());
+        assert!(line_range(text, Some(3), Some(2)).is_err());
+        assert!(line_range(text, None, Some(4)).is_err());
+    }
+
+    #[test]
+    fn segments_concatenated_licenses() {
+        // The same strategies as the gatherer uses with its default threshold
+        let store = crate::licenses::store_from_cache().unwrap();
+        let strat = askalono::ScanStrategy::new(&store)
+            .mode(askalono::ScanMode::Elimination)
+            .confidence_threshold(0.3)
+            .optimize(false)
+            .max_passes(1);
+        let segment = askalono::ScanStrategy::new(&store)
+            .mode(askalono::ScanMode::TopDown)
+            .confidence_threshold(0.8);
+
+        let segments = |contents: &str| {
+            segment_license_file(Path::new("LICENSE"), contents, &strat, &segment, 0.8)
+        };
+        let licenses = |segments: &[crate::licenses::LicenseFile]| {
+            segments
+                .iter()
+                .map(|lf| lf.license_expr.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let text = |id: &str| spdx::license_id(id).unwrap().text();
+
+        // Laid out like ring's LICENSE, the crate's own license followed by
+        // the licenses of the code it vendors, each with its copyright
+        let contents = format!(
+            "Note that it is easy for this file to get out of date with respect to the\n\
+            licenses of the vendored code.\n\n\
+            Copyright 2015-2016 Brian Smith.\n\n{}\n\n\
+            Copyright (c) 2015-2020 the fiat-crypto authors.\n\n{}\n\n\
+            Copyright (c) 1998-2011 The OpenSSL Project.\n\n{}",
+            text("MIT"),
+            text("ISC"),
+            text("OpenSSL"),
+        );
+
+        let found = segments(&contents).unwrap();
+        assert_eq!(licenses(&found), vec!["MIT", "ISC", "OpenSSL"]);
+
+        // Each license starts after its own copyright, and before the next one
+        let copyrights: Vec<_> = ["Brian Smith", "fiat-crypto", "OpenSSL Project"]
+            .iter()
+            .map(|holder| contents.find(holder).unwrap())
+            .chain(std::iter::once(contents.len()))
+            .collect();
+
+        let phrases = [
+            "Permission is hereby granted, free of charge",
+            "Permission to use, copy, modify, and/or distribute",
+            "OpenSSL Toolkit",
+        ];
+
+        for ((lf, bounds), phrase) in found.iter().zip(copyrights.windows(2)).zip(phrases) {
+            let range = lf.range.clone().unwrap();
+            assert!(
+                bounds[0] < range.start && range.start < bounds[1],
+                "'{}' starts at {}, not within {:?}",
+                lf.license_expr,
+                range.start,
+                bounds
+            );
+            assert!(contents[range].contains(phrase), "{}", lf.license_expr);
+        }
+
+        // Without anything between them, the top down scan stops at the first
+        // license, so the rest of the file needs to be scanned on its own
+        let contents = format!("{}\n\n{}\n", text("MIT"), text("ISC"));
+        assert_eq!(licenses(&segments(&contents).unwrap()), vec!["MIT", "ISC"]);
+
+        // The rest of the file after the first license can be too short for
+        // the top down scan, but match a license on its own
+        let without_copyright = |id: &str| {
+            text(id)
+                .lines()
+                .filter(|line| !line.starts_with("Copyright"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let contents = format!(
+            "Copyright 2015-2016 Brian Smith.\n\n{}\n\n\
+            Copyright (c) 2015-2020 the fiat-crypto authors.\n\n{}",
+            without_copyright("ISC"),
+            without_copyright("MIT"),
+        );
+        assert_eq!(licenses(&segments(&contents).unwrap()), vec!["ISC", "MIT"]);
+
+        assert!(segments("not a license\n").is_none());
+    }
+}
@@ -43,7 +43,16 @@ pub(crate) fn apply_workarounds<'krate>(
             if let Err(i) = super::binary_search(licensed_krates, krate) {
                 match retrieve_workaround(krate) {
                     Ok(Some(clarification)) => {
-                        match crate::licenses::apply_clarification(gc, krate, &clarification) {
+                        let provenance = super::Provenance::Workaround {
+                            name: workaround.clone(),
+                        };
+
+                        match crate::licenses::apply_clarification(
+                            gc,
+                            krate,
+                            &clarification,
+                            &provenance,
+                        ) {
                             Ok(files) => {
                                 log::debug!("applying workaround '{}' to '{}'", workaround, krate);
 
@@ -52,7 +61,10 @@ pub(crate) fn apply_workarounds<'krate>(
                                     KrateLicense {
                                         krate,
                                         lic_info: super::LicenseInfo::Expr(clarification.license),
+                                        provenance,
                                         license_files: files,
+                                        notices: Vec::new(),
+                                        copyrights: Vec::new(),
                                     },
                                 );
                             }